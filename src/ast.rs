@@ -1,37 +1,182 @@
+use std::collections::HashMap;
+use crate::span::Span;
 use crate::statement::Statement;
 
 /*
 This is for top level structures
  */
- 
+
+// a name declared with a type: fields, method args, and locals
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    pub typ: Type,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct Method {
     pub name: String,
-    pub args: Vec<(String, Type)>,
-    pub locals: Vec<(String, Type)>,
+    pub args: Vec<Declaration>,
+    pub locals: Vec<Declaration>,
     pub body: Vec<Statement>,
     pub return_type: Type,
+    pub span: Span,
+}
+
+// just the part of a method a caller needs to know about, interfaces only have these
+#[derive(Debug, Clone)]
+pub struct MethodSignature {
+    pub name: String,
+    pub args: Vec<Declaration>,
+    pub return_type: Type,
+    pub span: Span,
+}
+
+impl Method {
+    pub fn signature(&self) -> MethodSignature {
+        MethodSignature {
+            name: self.name.clone(),
+            args: self.args.clone(),
+            return_type: self.return_type.clone(),
+            span: self.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Class {
     pub name: String,
-    pub fields: Vec<(String, Type)>,
-    pub methods: Vec<Method>
+    // class Stack<T> has [T], empty for ordinary classes
+    pub type_params: Vec<String>,
+    // class B extends A, with where the A was written
+    pub superclass: Option<(String, Span)>,
+    // class B implements Shape, Named
+    pub interfaces: Vec<(String, Span)>,
+    pub fields: Vec<Declaration>,
+    // run by @A(args) right after the object is allocated, not inherited
+    pub constructor: Option<Method>,
+    pub methods: Vec<Method>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub name: String,
+    pub methods: Vec<MethodSignature>,
+    pub span: Span,
+}
+
+// enum Shape [ Circle(r:int) Rect(w:int, h:int) Empty ]
+// a value is one of the variants along with that variant's fields, @Shape.Circle(5)
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Declaration>,
+    pub span: Span,
+}
+
+impl Enum {
+    // the tag is the variant's position in the declaration
+    pub fn variant(&self, name: &str) -> Option<(usize, &Variant)> {
+        self.variants.iter().enumerate().find(|(_, variant)| variant.name == name)
+    }
+}
+
+// import "lib/list.441", the path is as written, relative to the importing file
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub imports: Vec<Import>,
     pub classes: Vec<Class>,
-    pub main_locals: Vec<(String, Type)>,
+    pub interfaces: Vec<Interface>,
+    pub enums: Vec<Enum>,
+    // top level functions, called as f(args) with no receiver
+    pub functions: Vec<Method>,
+    pub main_locals: Vec<Declaration>,
     pub main_body: Vec<Statement>
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Type {
     Int,
+    // true or false, stored as 1 or 0
+    Bool,
+    // an immutable string, laid out like an int[] of character codes
+    Str,
+    // a class or interface, or a type parameter like the T inside class Stack<T>
     ClassType(String),
+    // a generic class with its type arguments filled in, Stack<int>, Pair<str, A[]>
+    Generic(String, Vec<Type>),
+    // int[], A[], int[][]
+    Array(Box<Type>),
+    // A?, an object that might be null. a plain A never is
+    Nullable(Box<Type>),
+    // (int, A) -> bool, a lambda, function, or method reference taking those arguments
+    Function(Vec<Type>, Box<Type>),
 }
 
+impl Type {
+    // replaces every type parameter in map wherever it shows up, T[] -> int[] for T = int
+    pub fn substitute(&self, map: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::ClassType(name) => map.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Generic(name, args) => Type::Generic(name.clone(), args.iter().map(|arg| arg.substitute(map)).collect()),
+            Type::Array(element) => Type::Array(Box::new(element.substitute(map))),
+            // T? for an int or bool T is just the T, null is 0 the same way a new array element is,
+            // and T? for an A? T is still just A?
+            Type::Nullable(inner) => match inner.substitute(map) {
+                inner @ (Type::Int | Type::Bool | Type::Nullable(_)) => inner,
+                inner => Type::Nullable(Box::new(inner)),
+            },
+            Type::Function(args, ret) =>
+                Type::Function(args.iter().map(|arg| arg.substitute(map)).collect(), Box::new(ret.substitute(map))),
+            other => other.clone(),
+        }
+    }
 
+    // whether the type parameter name shows up anywhere in this type
+    pub fn mentions(&self, name: &str) -> bool {
+        match self {
+            Type::ClassType(other) => other == name,
+            Type::Generic(_, args) => args.iter().any(|arg| arg.mentions(name)),
+            Type::Array(inner) | Type::Nullable(inner) => inner.mentions(name),
+            Type::Function(args, ret) => args.iter().any(|arg| arg.mentions(name)) || ret.mentions(name),
+            _ => false,
+        }
+    }
+}
 
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "str"),
+            Type::ClassType(name) => write!(f, "{}", name),
+            Type::Generic(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            Type::Array(element) => write!(f, "{}[]", element),
+            Type::Nullable(inner) => write!(f, "{}?", inner),
+            Type::Function(args, ret) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "({}) -> {}", args.join(", "), ret)
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use crate::expression::{Expression, ExpressionKind, Lambda};
+use crate::span::Span;
+use crate::statement::{Statement, StatementKind};
+
+/*
+A lambda gets a copy of every variable from around it that its body uses:
+
+    var k = 10
+    var addK = function(x:int) returning int: { return x + k }
+    k = 20
+    print(addK(1))          # 11, addK kept the k from when it was made
+
+x is the lambda's own argument so it isnt captured, k is. this counts as a variable too,
+so a lambda inside a method can still use its object. The copies are taken once, when the lambda
+is made, which is why the body cant assign to one (it would only change the copy).
+
+This only looks at names. A name that isnt a variable where the lambda is made (a top level
+function, or a typo) shows up here too, and the type checker and ir builder skip those.
+*/
+
+pub struct Capture {
+    pub name: String,
+    // the first place the body uses it
+    pub span: Span,
+    // the first place the body assigns to it, if it does
+    pub assigned: Option<Span>,
+}
+
+// every name the lambda uses from outside itself, in the order they first show up
+pub fn captures(lambda: &Lambda) -> Vec<Capture> {
+    let mut finder = Finder { scopes: vec![], found: vec![] };
+    finder.lambda(lambda);
+    finder.found
+}
+
+struct Finder {
+    // names the lambda declares itself, one set per open block, innermost last
+    scopes: Vec<HashSet<String>>,
+    found: Vec<Capture>,
+}
+
+impl Finder {
+    fn lambda(&mut self, lambda: &Lambda) {
+        self.scopes.push(lambda.args.iter().map(|arg| arg.name.clone()).collect());
+        self.block(&lambda.body);
+        self.scopes.pop();
+    }
+
+    fn use_name(&mut self, name: &str, span: Span, assigned: bool) {
+        if self.scopes.iter().any(|scope| scope.contains(name)) {
+            return;
+        }
+        let assigned = assigned.then_some(span);
+        match self.found.iter_mut().find(|capture| capture.name == name) {
+            Some(capture) => capture.assigned = capture.assigned.or(assigned),
+            None => self.found.push(Capture { name: name.to_string(), span, assigned }),
+        }
+    }
+
+    // a block scope holding just these names, for match bindings and catch variables
+    fn block_with(&mut self, names: HashSet<String>, statements: &[Statement]) {
+        self.scopes.push(names);
+        self.block(statements);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashSet::new());
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Assignment { variable, expression } => {
+                self.expression(expression);
+                self.use_name(variable, statement.span, true);
+            }
+            // the initializer cant see the name it declares yet
+            StatementKind::Declaration { name, value, .. } => {
+                self.expression(value);
+                self.scopes.last_mut().unwrap().insert(name.clone());
+            }
+            StatementKind::Discard(expression)
+            | StatementKind::Return(expression)
+            | StatementKind::Print(expression)
+            | StatementKind::Throw(expression) => self.expression(expression),
+            StatementKind::FieldWrite { base, value, .. } => {
+                self.expression(base);
+                self.expression(value);
+            }
+            StatementKind::ArrayWrite { array, index, value } => {
+                self.expression(array);
+                self.expression(index);
+                self.expression(value);
+            }
+            StatementKind::If { condition, then_body, else_body } => {
+                self.expression(condition);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            StatementKind::IfOnly { condition, body } | StatementKind::While { condition, body } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            // the loop assigns its variable every pass
+            StatementKind::For { variable, from, to, body } => {
+                self.use_name(variable, statement.span, true);
+                self.expression(from);
+                self.expression(to);
+                self.block(body);
+            }
+            StatementKind::Match { value, arms } => {
+                self.expression(value);
+                for arm in arms {
+                    let bindings = arm.bindings.iter().filter(|name| *name != "_").cloned().collect();
+                    self.block_with(bindings, &arm.body);
+                }
+            }
+            StatementKind::Try { body, catches } => {
+                self.block(body);
+                for catch in catches {
+                    self.block_with(HashSet::from([catch.name.clone()]), &catch.body);
+                }
+            }
+            StatementKind::Break | StatementKind::Continue => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => self.use_name(name, expression.span, false),
+            ExpressionKind::ThisExpr => self.use_name("this", expression.span, false),
+            // calling a function value uses the variable holding it
+            ExpressionKind::FunctionCall { function_name, .. } => self.use_name(function_name, expression.span, false),
+            // whatever a lambda in here captures, this one has to capture for it to copy
+            ExpressionKind::Lambda(inner) => self.lambda(inner),
+            _ => {}
+        }
+        for operand in expression.operands() {
+            self.expression(operand);
+        }
+    }
+}
@@ -2,11 +2,9 @@ use crate::ir::{ControlTransfer, Function, Primitive, Value};
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CFG {
 
-    // bb label -> index in function.blocks
-    block_map: HashMap<String, usize>,
-
     // 2d array
     // index of successor bb -> indices of predecessor bbs
     predecessors: Vec<Vec<usize>>,
@@ -66,7 +64,6 @@ impl CFG {
         }
 
         CFG { 
-            block_map,
             predecessors,
             successors,
             entry: 0,
@@ -83,6 +80,11 @@ impl CFG {
         let mut stacks: HashMap<String, Vec<String>> = HashMap::new();
         let mut counter: usize = 0;
 
+        // arguments already have a value on the way in, their first version is just themselves
+        for arg in &function.args {
+            stacks.insert(arg.clone(), vec![arg.clone()]);
+        }
+
         self.rename(function, self.entry, &mut stacks, &mut counter, &tree, var_types);
     }
 
@@ -105,8 +107,8 @@ impl CFG {
         // the iteration will shrink the sets down with the algo above
         // this prevents any dominator information loss
         let all_blocks: HashSet<usize> = (0..self.num_blocks).collect();
-        for idx in 1..self.num_blocks {
-            dominator_sets[idx] = all_blocks.clone();
+        for dominator_set in dominator_sets.iter_mut().skip(1) {
+            *dominator_set = all_blocks.clone();
         }
 
         // fixed point iter: just keep going until we dont see a change
@@ -253,7 +255,7 @@ impl CFG {
             for primitive in &block.primitives {
                 if let Primitive::Assign { dest, .. } = primitive {
                     assignments.entry(dest.clone())
-                        .or_insert_with(HashSet::new)
+                        .or_default()
                         .insert(idx);
                 }
             }
@@ -295,7 +297,7 @@ impl CFG {
                         // variable "var"
                         phis
                             .entry(frontier)
-                            .or_insert_with(HashSet::new)
+                            .or_default()
                             .insert(var.clone());
                         work_stack.push(frontier);
                     }
@@ -374,12 +376,15 @@ impl CFG {
                 let new_name = counter.to_string();
                 *counter += 1;
 
-                if let Some(typ) = var_types.get(&old_name).cloned() {
-                    var_types.insert(new_name.clone(), typ);
-                }
+                // the numbered names start over in every function, so drop whatever type
+                // an earlier function left under this one
+                match var_types.get(&old_name).cloned() {
+                    Some(typ) => var_types.insert(new_name.clone(), typ),
+                    None => var_types.remove(&new_name),
+                };
 
                 *assignment = new_name.clone();
-                stacks.entry(old_name.clone()).or_insert_with(Vec::new).push(new_name);
+                stacks.entry(old_name.clone()).or_default().push(new_name);
                 *pushed.entry(old_name).or_insert(0) += 1;
             }
         }
@@ -405,15 +410,15 @@ impl CFG {
                     // every phi is (label, value) pair
                     //  ex: x = phi(then5, x, else6, x) where then5 and else6 are predecessor labels
                     for (label, val) in args {
-                        if label == &this_label {
-                            if let Value::Variable(var_name) = val {
-
-                                // look up original variable name, and replace it with the current ssa version
-                                if let Some(stack) = stacks.get(var_name.as_str()) {
-                                    if let Some(current) = stack.last() {
-                                        *var_name = current.clone();
-                                    }
-                                }
+                        // look up original variable name, and replace it with the current ssa version
+                        if label == &this_label
+                            && let Value::Variable(var_name) = val {
+                            match stacks.get(var_name.as_str()).and_then(|stack| stack.last()) {
+                                Some(current) => *var_name = current.clone(),
+                                // not defined on the way in from this block, like a temp first assigned
+                                // inside a loop reaching the phi at the loop header from before the loop.
+                                // nothing reads the phi along that edge, it just needs some value
+                                None => *val = Value::Constant(0),
                             }
                         }
                     }
@@ -436,7 +441,11 @@ impl CFG {
 
     // constant folding - pretty self explanatory
     // for now just doing it in a separate pass, after ssa
+    // without ssa a variable can be assigned more than once (loop counters, and/or results),
+    // so only variables with a single definition are treated as constants
     pub fn fold_constants(&mut self, function: &mut Function) {
+        let definitions = Self::count_definitions(function);
+        let single = |var: &String| definitions.get(var) == Some(&1);
         let mut changed = true;
 
         while changed {
@@ -446,9 +455,17 @@ impl CFG {
             
             for block in &mut function.blocks {
                 for i in 0..block.primitives.len() {
-                    if let Primitive::Assign { dest, value: Value::Constant(c) } = &block.primitives[i] {
+                    if let Primitive::Assign { dest, value: Value::Constant(c) } = &block.primitives[i]
+                        && single(dest) {
                         const_map.insert(dest.clone(), *c);
                     }
+
+                    // copies of a known constant are known too, value numbering leaves a lot of these
+                    if let Primitive::Assign { dest, value: Value::Variable(v) } = &block.primitives[i]
+                        && single(dest)
+                        && let Some(&c) = const_map.get(v) {
+                        const_map.insert(dest.clone(), c);
+                    }
                     
                     if let Some(folded) = Self::try_fold_constant(&block.primitives[i], &const_map) {
                         block.primitives[i] = folded;
@@ -459,6 +476,27 @@ impl CFG {
         }
     }
 
+    // variable -> how many primitives assign to it
+    fn count_definitions(function: &Function) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for block in &function.blocks {
+            for primitive in &block.primitives {
+                let dest = match primitive {
+                    Primitive::Assign { dest, .. }
+                    | Primitive::BinOp { dest, .. }
+                    | Primitive::Call { dest, .. }
+                    | Primitive::Phi { dest, .. }
+                    | Primitive::Alloc { dest, .. }
+                    | Primitive::GetElt { dest, .. }
+                    | Primitive::Load { dest, .. } => dest,
+                    Primitive::Print { .. } | Primitive::SetElt { .. } | Primitive::Store { .. } => continue,
+                };
+                *counts.entry(dest.clone()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
     fn try_fold_constant(prim: &Primitive, const_map: &HashMap<String, i64>) -> Option<Primitive> {
         match prim {
             Primitive::BinOp { dest, lhs, op, rhs } => {
@@ -474,13 +512,12 @@ impl CFG {
                     _ => None,
                 };
                 
-                if let (Some(left), Some(right)) = (left_val, right_val) {
-                    if let Some(result) = Self::evaluate_binop(op, left, right) {
-                        return Some(Primitive::Assign {
-                            dest: dest.clone(),
-                            value: Value::Constant(result),
-                        });
-                    }
+                if let (Some(left), Some(right)) = (left_val, right_val)
+                    && let Some(result) = Self::evaluate_binop(op, left, right) {
+                    return Some(Primitive::Assign {
+                        dest: dest.clone(),
+                        value: Value::Constant(result),
+                    });
                 }
                 None
             }
@@ -488,25 +525,64 @@ impl CFG {
         }
     }
 
+    // fold the same way the ir executes it: every value is an unsigned 64 bit word,
+    // so comparisons, division and >> treat negative constants as huge numbers.
+    // the builder never emits % <= >= != but they fold too in case that changes
     fn evaluate_binop(op: &str, left: i64, right: i64) -> Option<i64> {
-        match op {
-            "+" => Some(left.wrapping_add(right)),
-            "-" => Some(left.wrapping_sub(right)),
-            "*" => Some(left.wrapping_mul(right)),
-            "/" => {
-                if right == 0 {
-                    None
-                } else {
-                    Some(left / right)
-                }
+        let (l, r) = (left as u64, right as u64);
+        let result = match op {
+            "+" => l.wrapping_add(r),
+            "-" => l.wrapping_sub(r),
+            "*" => l.wrapping_mul(r),
+            "/" => l.checked_div(r)?,
+            "%" => l.checked_rem(r)?,
+            "|" => l | r,
+            "&" => l & r,
+            "^" => l ^ r,
+            // the shift amount wraps at 64 like the machine does
+            "<<" => l.wrapping_shl(r as u32),
+            ">>" => l.wrapping_shr(r as u32),
+            "==" => (l == r) as u64,
+            "!=" => (l != r) as u64,
+            "<" => (l < r) as u64,
+            ">" => (l > r) as u64,
+            "<=" => (l <= r) as u64,
+            ">=" => (l >= r) as u64,
+            _ => return None,
+        };
+        Some(result as i64)
+    }
+
+    // a bool temp can only hold 0 or 1, so comparing it against a constant just gives it back
+    // (or its flip). this mostly catches source like `x == true` and `done == false`
+    //      %b == 1, %b > 0, %b & 1  ->  %b
+    //      %b == 0, %b < 1          ->  %b ^ 1
+    pub fn simplify_bools(&mut self, function: &mut Function, var_types: &HashMap<String, crate::ast::Type>) {
+        let is_bool = |val: &Value| matches!(val, Value::Variable(v) if var_types.get(v) == Some(&crate::ast::Type::Bool));
+
+        for block in &mut function.blocks {
+            for primitive in &mut block.primitives {
+                let Primitive::BinOp { dest, lhs, op, rhs } = primitive else { continue };
+
+                // put the bool on the left, == and & dont care about the order
+                let (b, c, op) = match (&*lhs, &*rhs) {
+                    (b, Value::Constant(c)) if is_bool(b) => (b.clone(), *c, op.as_str()),
+                    (Value::Constant(c), b) if is_bool(b) && (op == "==" || op == "&") => (b.clone(), *c, op.as_str()),
+                    _ => continue,
+                };
+
+                let simplified = match (op, c) {
+                    ("==", 1) | (">", 0) | ("&", 1) => Primitive::Assign { dest: dest.clone(), value: b },
+                    ("==", 0) | ("<", 1) => Primitive::BinOp {
+                        dest: dest.clone(),
+                        lhs: b,
+                        op: "^".to_string(),
+                        rhs: Value::Constant(1),
+                    },
+                    _ => continue,
+                };
+                *primitive = simplified;
             }
-            "|" => Some(left | right),
-            "&" => Some(left & right),
-            "^" => Some(left ^ right),
-            "==" => Some(if left == right { 1 } else { 0 }),
-            "<" => Some(if left < right { 1 } else { 0 }),
-            ">" => Some(if left > right { 1 } else { 0 }),
-            _ => None,
         }
     }
 
@@ -571,9 +647,7 @@ impl CFG {
                         // ex: %a = %b or %a = 5
                         let vn = Self::get_valnum(value, &mut var_to_valnum, &mut const_to_valnum, &mut valnum_to_var, &mut valnum_count);
                         var_to_valnum.insert(dest.clone(), vn);
-                        if !valnum_to_var.contains_key(&vn) {
-                            valnum_to_var.insert(vn, dest.clone());
-                        }
+                        valnum_to_var.entry(vn).or_insert_with(|| dest.clone());
                     }
 
                     Primitive::Load { dest, .. } |
@@ -623,7 +697,7 @@ impl CFG {
             // globals are treated like variables
             // ie global @vtablA gets the same value number
             // so expressions witht hose work
-            Value::Global(name) => {
+            Value::Global(name) | Value::Code(name) => {
                 if let Some(&valnum) = var_to_valnum.get(name) {
                     valnum
                 } else {
@@ -717,11 +791,9 @@ fn rename_uses(prim: &mut Primitive, stacks: &HashMap<String, Vec<String>>) {
 }
 
 fn rename_value(val: &mut Value, stacks: &HashMap<String, Vec<String>>) {
-    if let Value::Variable(name) = val {
-        if let Some(stack) = stacks.get(name) {
-            if let Some(current) = stack.last() {
-                *name = current.clone();
-            }
-        }
+    if let Value::Variable(name) = val
+        && let Some(stack) = stacks.get(name)
+        && let Some(current) = stack.last() {
+        *name = current.clone();
     }
 }
\ No newline at end of file
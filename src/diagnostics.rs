@@ -0,0 +1,240 @@
+use crate::parser::ParseError;
+use crate::span::Span;
+use crate::typechecker::TypeError;
+
+/*
+Everything the compiler wants to tell the user about the source goes through here.
+Parse and type errors (and type checker warnings) get turned into a Diagnostic, and then rendered either
+rustc style for people:
+
+    error[E0012]: cannot store into field `A.n`
+     --> prog.441:14:12
+       |
+    14 |     !a.n = 5
+       |            ^ expected A, found int
+       |
+    note: field `n` declared here
+     --> prog.441:2:19
+       |
+     2 |     fields x:int, n:A
+       |                   ^^^
+
+or as one json object per line for editors and ci (--error-format=json)
+
+A program can be spread over several files through imports, so every span says which file it is in
+(an index into the list of files, the root file being 0) and gets printed against that file's text
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+// warnings get printed but dont stop the compile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+    // short text printed under the caret
+    pub label: Option<String>,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+    pub notes: Vec<Note>,
+}
+
+// extra context pointing somewhere else in the source, ex: field `next` declared here
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Span,
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let expected = if error.expected.is_empty() {
+            None
+        } else {
+            Some(error.expected.iter().map(|t| t.to_string()).collect::<Vec<String>>().join(" or "))
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: error.to_string(),
+            span: error.span,
+            label: None,
+            expected,
+            found: Some(error.found.to_string()),
+            notes: vec![],
+        }
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(error: &TypeError) -> Self {
+        let label = match (&error.expected, &error.found) {
+            (Some(expected), Some(found)) => Some(format!("expected {}, found {}", expected, found)),
+            _ => None,
+        };
+        Diagnostic {
+            severity: error.code.severity(),
+            code: Some(error.code.as_str()),
+            message: error.message.clone(),
+            span: error.span,
+            label,
+            expected: error.expected.as_ref().map(|t| t.to_string()),
+            found: error.found.as_ref().map(|t| t.to_string()),
+            notes: error.notes.iter()
+                .map(|(message, span)| Note { message: message.clone(), span: *span })
+                .collect(),
+        }
+    }
+}
+
+// prints every diagnostic to stderr in the requested format
+// files[0] is the file being compiled, the rest are its imports
+pub fn emit(diagnostics: &[Diagnostic], files: &[SourceFile], format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", render_human(diagnostic, files));
+            }
+            let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+            let warnings = diagnostics.len() - errors;
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            if errors > 0 {
+                eprintln!("error: could not compile `{}` due to {} error{}", files[0].name, errors, plural(errors));
+            } else if warnings > 0 {
+                eprintln!("warning: `{}` generated {} warning{}", files[0].name, warnings, plural(warnings));
+            }
+        }
+        ErrorFormat::Json => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", render_json(diagnostic, files));
+            }
+        }
+    }
+}
+
+pub fn render_human(diagnostic: &Diagnostic, files: &[SourceFile]) -> String {
+    let mut out = String::new();
+    let severity = diagnostic.severity.as_str();
+    match diagnostic.code {
+        Some(code) => out.push_str(&format!("{}[{}]: {}\n", severity, code, diagnostic.message)),
+        None => out.push_str(&format!("{}: {}\n", severity, diagnostic.message)),
+    }
+    out.push_str(&snippet(diagnostic.span, diagnostic.label.as_deref(), files));
+
+    for note in &diagnostic.notes {
+        out.push_str(&format!("note: {}\n", note.message));
+        out.push_str(&snippet(note.span, None, files));
+    }
+    out
+}
+
+// the --> location line, the source line, and a caret underline under the span
+fn snippet(span: Span, label: Option<&str>, files: &[SourceFile]) -> String {
+    let file = &files[span.file];
+    let line_text = file.text.lines().nth(span.line.saturating_sub(1)).unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+
+    // only underline the first line of spans that cover several lines, like a whole method
+    let start_col = (span.col - 1).min(line_text.len());
+    let end_col = (start_col + span.end.saturating_sub(span.start)).min(line_text.len());
+
+    // keep tabs as tabs so the caret lines up with the source line above it
+    let padding: String = line_text[..start_col].chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = line_text[start_col..end_col].chars().count().max(1);
+
+    let mut out = String::new();
+    out.push_str(&format!("{}--> {}:{}:{}\n", gutter, file.name, span.line, span.col));
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!("{} | {}\n", span.line, line_text));
+    out.push_str(&format!("{} | {}{}", gutter, padding, "^".repeat(width)));
+    if let Some(label) = label {
+        out.push_str(&format!(" {}", label));
+    }
+    out.push('\n');
+    out.push_str(&format!("{} |\n", gutter));
+    out
+}
+
+pub fn render_json(diagnostic: &Diagnostic, files: &[SourceFile]) -> String {
+    let notes: Vec<String> = diagnostic.notes.iter()
+        .map(|note| format!("{{\"message\":{},\"span\":{}}}", json_string(&note.message), json_span(note.span, files)))
+        .collect();
+
+    format!(
+        "{{\"severity\":\"{}\",\"code\":{},\"message\":{},\"span\":{},\"label\":{},\"expected\":{},\"found\":{},\"notes\":[{}]}}",
+        diagnostic.severity.as_str(),
+        json_option(diagnostic.code),
+        json_string(&diagnostic.message),
+        json_span(diagnostic.span, files),
+        json_option(diagnostic.label.as_deref()),
+        json_option(diagnostic.expected.as_deref()),
+        json_option(diagnostic.found.as_deref()),
+        notes.join(","),
+    )
+}
+
+fn json_span(span: Span, files: &[SourceFile]) -> String {
+    let file = &files[span.file];
+    // the span only stores where it starts, so work out where the end lands
+    let end = span.end.min(file.text.len());
+    let before_end = &file.text[..end];
+    let end_line = before_end.matches('\n').count() + 1;
+    let end_col = end - before_end.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+    format!(
+        "{{\"file\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{},\"byte_start\":{},\"byte_end\":{}}}",
+        json_string(&file.name), span.line, span.col, end_line, end_col, span.start, span.end,
+    )
+}
+
+fn json_option(value: Option<&str>) -> String {
+    match value {
+        Some(value) => json_string(value),
+        None => "null".to_string(),
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,9 +1,55 @@
-use crate::token::Operator;
+use crate::ast::{Declaration, Type};
+use crate::span::Span;
+use crate::statement::Statement;
+use crate::token::{Operator, UnaryOperator};
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+
+    // the expressions directly inside this one, a lambda's body doesnt count since it runs later
+    pub fn operands(&self) -> Vec<&Expression> {
+        match &self.kind {
+            ExpressionKind::Binop { lhs, rhs, .. } => vec![lhs, rhs],
+            ExpressionKind::Unary { operand, .. } => vec![operand],
+            ExpressionKind::MethodCall { base, args, .. } => std::iter::once(base.as_ref()).chain(args).collect(),
+            ExpressionKind::FunctionCall { args, .. }
+            | ExpressionKind::ClassRef { args, .. }
+            | ExpressionKind::Variant { args, .. } => args.iter().collect(),
+            ExpressionKind::FieldRead { base, .. } | ExpressionKind::MethodRef { base, .. } => vec![base],
+            ExpressionKind::FieldWrite { base, value, .. } => vec![base, value],
+            ExpressionKind::ArrayAlloc { length, .. } => vec![length],
+            ExpressionKind::Index { array, index } => vec![array, index],
+            ExpressionKind::Length(array) => vec![array],
+            ExpressionKind::ThisExpr | ExpressionKind::Constant(_) | ExpressionKind::Bool(_)
+            | ExpressionKind::StringLiteral(_) | ExpressionKind::Variable(_) | ExpressionKind::Null(_)
+            | ExpressionKind::Lambda(_) => vec![],
+        }
+    }
+}
+
+// function(x:int) returning int: { return x + k }
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub args: Vec<Declaration>,
+    pub return_type: Type,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     ThisExpr,
     Constant(i64),
+    // true or false
+    Bool(bool),
+    StringLiteral(String),
     Binop {
         // here, Box is a heap pointer with size 8 bytes
         // Need this here for recursie types to avoid infinite size at compile time
@@ -11,11 +57,20 @@ pub enum Expression {
         op: Operator,
         rhs: Box<Expression>,
     },
+    Unary {
+        op: UnaryOperator,
+        operand: Box<Expression>,
+    },
     MethodCall {
         base: Box<Expression>,
         method_name: String,
         args: Vec<Expression>,
     },
+    // f(args), a top level function, or a variable holding a function value
+    FunctionCall {
+        function_name: String,
+        args: Vec<Expression>,
+    },
     FieldRead {
         base: Box<Expression>,
         field_name: String,
@@ -25,7 +80,39 @@ pub enum Expression {
         field_name: String,
         value: Box<Expression>,
     },
-    ClassRef(String),
+    // @A or @A(args), args go to the constructor
+    // @Stack<int>(args) for a generic class, type_args is empty otherwise
+    ClassRef {
+        class_name: String,
+        type_args: Vec<Type>,
+        args: Vec<Expression>,
+    },
+    // @Shape.Circle(5), a new enum value, args are the variant's fields in order
+    Variant {
+        enum_name: String,
+        variant: String,
+        args: Vec<Expression>,
+    },
     Variable(String),
-    Null(String),
+    // null:A or null:Stack<int>
+    Null(Type),
+    // @int[n], a new array of n elements that all start as 0/null
+    ArrayAlloc {
+        element: Type,
+        length: Box<Expression>,
+    },
+    // a[i]
+    Index {
+        array: Box<Expression>,
+        index: Box<Expression>,
+    },
+    // len(a)
+    Length(Box<Expression>),
+    // a function value, it gets a copy of every variable from around it that its body uses
+    Lambda(Lambda),
+    // ^obj.m without the (args), m bound to obj as a function value
+    MethodRef {
+        base: Box<Expression>,
+        method_name: String,
+    },
 }
\ No newline at end of file
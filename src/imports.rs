@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+use crate::ast::{Import, Program};
+use crate::diagnostics::{Diagnostic, SourceFile};
+use crate::parser::Parser;
+use crate::span::Span;
+use crate::tokenizer::Tokenizer;
+use crate::typechecker::{ErrorCode, TypeError};
+
+/*
+A program can be split over several files:
+
+    import "lib/list.441"
+    class Stack [ fields top:ListNode? ... ]
+    main with s:Stack: ...
+
+The path is looked up next to the importing file first, then in each -I search path in order.
+An imported file only has declarations (no main), and can import more files itself.
+Everything gets loaded up front and glued into one Program, imported declarations first,
+so nothing after this knows there was more than one file. A file imported twice
+(say two libraries both importing list.441) is only read once.
+
+Each file gets an index, the root file is 0, and the tokenizer stamps it on every span,
+so errors point into the right file.
+*/
+
+// every file that was read (root first) along with the merged program, or everything that went wrong
+pub fn load(root_name: &str, root_text: String, search_paths: &[PathBuf]) -> (Vec<SourceFile>, Result<Program, Vec<Diagnostic>>) {
+    let mut loader = Loader {
+        search_paths,
+        files: vec![SourceFile { name: root_name.to_string(), text: root_text }],
+        paths: vec![canonical(Path::new(root_name))],
+        loading: vec![],
+        programs: vec![],
+        diagnostics: vec![],
+    };
+    loader.load_file(0);
+    // with a file missing there could be duplicates we cant see, so only look once everything loaded
+    if loader.diagnostics.is_empty() {
+        loader.check_duplicates();
+    }
+
+    let result = if loader.diagnostics.is_empty() {
+        Ok(loader.merge())
+    } else {
+        Err(std::mem::take(&mut loader.diagnostics))
+    };
+    (loader.files, result)
+}
+
+struct Loader<'a> {
+    search_paths: &'a [PathBuf],
+    files: Vec<SourceFile>,
+    // the canonical path of each file, so lib/../lib/list.441 and lib/list.441 are the same file
+    paths: Vec<PathBuf>,
+    // files whose imports are still being loaded, importing one of these again is a cycle
+    loading: Vec<usize>,
+    // parsed files in the order they finished loading, so every file comes after what it imports
+    programs: Vec<Program>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Loader<'_> {
+    fn load_file(&mut self, file: usize) {
+        let tokenizer = Tokenizer::new(self.files[file].text.clone(), file);
+        let program = match Parser::new(tokenizer).parse_program(file == 0) {
+            Ok(program) => program,
+            Err(errors) => {
+                self.diagnostics.extend(errors.iter().map(Diagnostic::from));
+                return;
+            }
+        };
+
+        self.loading.push(file);
+        for import in &program.imports {
+            self.import(file, import);
+        }
+        self.loading.pop();
+        self.programs.push(program);
+    }
+
+    fn import(&mut self, from: usize, import: &Import) {
+        let Some(path) = self.resolve(from, &import.path) else {
+            let mut message = format!("cannot find `{}` next to `{}`", import.path, self.files[from].name);
+            if !self.search_paths.is_empty() {
+                message.push_str(" or in any -I search path");
+            }
+            self.error(ErrorCode::ImportNotFound, message, import.span, vec![]);
+            return;
+        };
+
+        let path_key = canonical(&path);
+        if let Some(file) = self.paths.iter().position(|other| *other == path_key) {
+            // already loaded, unless it is one of the files importing us
+            if let Some(position) = self.loading.iter().position(|loading| *loading == file) {
+                let mut chain: Vec<&str> = self.loading[position..].iter()
+                    .map(|loading| self.files[*loading].name.as_str())
+                    .collect();
+                chain.push(&self.files[file].name);
+                let message = format!("import cycle: {}", chain.join(" -> "));
+                self.error(ErrorCode::ImportCycle, message, import.span, vec![]);
+            }
+            return;
+        }
+
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            self.error(ErrorCode::ImportNotFound, format!("could not read `{}`", path.display()), import.span, vec![]);
+            return;
+        };
+        self.files.push(SourceFile { name: path.display().to_string(), text });
+        self.paths.push(path_key);
+        self.load_file(self.files.len() - 1);
+    }
+
+    // the directory of the importing file wins over the search paths
+    fn resolve(&self, from: usize, path: &str) -> Option<PathBuf> {
+        let directory = Path::new(&self.files[from].name).parent().unwrap_or(Path::new(""));
+        std::iter::once(directory)
+            .chain(self.search_paths.iter().map(|path| path.as_path()))
+            .map(|directory| directory.join(path))
+            .find(|candidate| candidate.is_file())
+    }
+
+    // two files declaring the same class, interface, or enum (or function) is an error even if the declarations match
+    // duplicates inside one file are left to the type checker, which reports those the same way it always has
+    fn check_duplicates(&mut self) {
+        let types: Vec<(&String, Span)> = self.programs.iter()
+            .flat_map(|program| program.classes.iter().map(|c| (&c.name, c.span))
+                .chain(program.interfaces.iter().map(|i| (&i.name, i.span)))
+                .chain(program.enums.iter().map(|e| (&e.name, e.span))))
+            .collect();
+        let functions: Vec<(&String, Span)> = self.programs.iter()
+            .flat_map(|program| program.functions.iter().map(|f| (&f.name, f.span)))
+            .collect();
+
+        let mut errors = vec![];
+        for (code, what, declared) in [(ErrorCode::DuplicateClass, "", types), (ErrorCode::DuplicateFunction, "function ", functions)] {
+            for (i, (name, span)) in declared.iter().enumerate() {
+                let first = declared[..i].iter().find(|(other, first)| other == name && first.file != span.file);
+                if let Some((_, first)) = first {
+                    let message = format!("{}`{}` is declared in both `{}` and `{}`",
+                        what, name, self.files[first.file].name, self.files[span.file].name);
+                    errors.push((code, message, *span, vec![(format!("`{}` first declared here", name), *first)]));
+                }
+            }
+        }
+        for (code, message, span, notes) in errors {
+            self.error(code, message, span, notes);
+        }
+    }
+
+    // the root file finishes loading last, so its main is the last program's
+    fn merge(&mut self) -> Program {
+        let mut root = self.programs.pop().unwrap();
+        let mut program = Program {
+            imports: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            enums: vec![],
+            functions: vec![],
+            main_locals: std::mem::take(&mut root.main_locals),
+            main_body: std::mem::take(&mut root.main_body),
+        };
+        for part in self.programs.drain(..).chain(std::iter::once(root)) {
+            program.imports.extend(part.imports);
+            program.classes.extend(part.classes);
+            program.interfaces.extend(part.interfaces);
+            program.enums.extend(part.enums);
+            program.functions.extend(part.functions);
+        }
+        program
+    }
+
+    fn error(&mut self, code: ErrorCode, message: String, span: Span, notes: Vec<(String, Span)>) {
+        let error = TypeError { code, message, span, expected: None, found: None, notes };
+        self.diagnostics.push(Diagnostic::from(&error));
+    }
+}
+
+// falls back to the path itself if it cant be resolved, the file gets read right after anyway
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
@@ -6,7 +6,9 @@ use crate::ast;
 pub enum Value {
     Constant(i64),
     Variable(String),
-    Global(String)
+    Global(String),
+    // the code address of a function, written as just its name, ex: call(_allocArray, 0, %n)
+    Code(String),
 }
 
 #[derive(Debug, Clone)]
@@ -267,11 +269,15 @@ impl Program {
 
     fn format_value(&self, value: &Value) -> String {
         match value {
-            Value::Constant(num) => num.to_string(),
+            // the ir's ints are unsigned 64 bit and it cant parse a leading minus,
+            // so negative constants go out as their two's complement bit pattern
+            Value::Constant(num) => (*num as u64).to_string(),
 
             Value::Variable(var) => format!("%{}", var),
 
             Value::Global(global) => format!("@{}", global),
+
+            Value::Code(function) => function.clone(),
         }
     }
 
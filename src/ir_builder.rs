@@ -1,9 +1,36 @@
-use crate::expression::Expression;
-use crate::statement::Statement;
+use crate::captures::captures;
+use crate::expression::{Expression, ExpressionKind, Lambda};
+use crate::statement::{Catch, Statement, StatementKind};
 use crate::ast;
 use crate::ir::{self, BasicBlock, Function, Primitive, Value, ControlTransfer, GlobalArray};
-use crate::token::Operator;
-use std::collections::HashMap;
+use crate::token::{Operator, UnaryOperator};
+use crate::typechecker::always_exits;
+use std::collections::{BTreeSet, HashMap};
+
+// runtime helper that allocates an array whose length isnt known until runtime
+// starts with _ so it can never clash with a method's {method}{Class} name
+const ARRAY_ALLOC_FUNCTION: &str = "_allocArray";
+
+// top level function f becomes fn_f in the ir, source names cant contain _ so
+// this cant clash with a method's {method}{Class} name either
+const FUNCTION_PREFIX: &str = "fn_";
+
+// the biggest array _allocArray can hand out is 2^this elements
+const MAX_ARRAY_SIZE_CLASS: u32 = 20;
+
+// one slot on the heap holding the object currently being thrown, or 0 when nothing is.
+// main allocates it and every method and function gets a pointer to it right after this
+// (ir globals are read only, so it cant just be a global)
+const EXCEPTION_STATE: &str = "exceptionState";
+
+// lambda n becomes the ir function _lambdaN, and a method reference taking n arguments
+// is called through _boundMethodN, which passes the call on to the method
+const LAMBDA_PREFIX: &str = "_lambda";
+const BOUND_METHOD_PREFIX: &str = "_boundMethod";
+
+// runtime helpers for strings, emitted once if the program uses them
+const PRINT_STR_FUNCTION: &str = "_printStr";
+const STR_EQUALS_FUNCTION: &str = "_strEquals";
 
 pub struct IRBuilder {
     temp_counter: usize,
@@ -19,12 +46,50 @@ pub struct IRBuilder {
     global_field_ids: HashMap<String, usize>,
     global_method_ids: HashMap<String, usize>,
 
-    current_block_has_explicit_return: bool,
+    // set once a return, break, or continue has decided where the current block goes
+    current_block_has_explicit_transfer: bool,
+
+    // source name -> ir name for variables declared inside blocks, one map per open block, innermost last.
+    // every declaration gets a fresh xN like any other temp, so shadowed names never share an ir variable
+    scopes: Vec<HashMap<String, String>>,
+
+    // (break target, continue target) for every loop we're inside of, innermost last
+    loop_labels: Vec<(String, String)>,
+
+    // only programs that throw pay for passing the exception state around and checking it after calls
+    uses_exceptions: bool,
+    // where a throw goes: the catches of every try we're inside of, innermost last
+    handlers: Vec<String>,
+    // the block that passes an exception on to the caller, made the first time the current function needs it
+    unwind_label: Option<String>,
 
     type_environment: HashMap<String, ast::Type>,
     classes: Vec<ast::Class>,
+    interfaces: Vec<ast::Interface>,
+    enums: Vec<ast::Enum>,
+    top_level_functions: Vec<ast::Method>,
 
     var_types: HashMap<String, ast::Type>,
+
+    // only emit _allocArray when something actually allocates an array
+    uses_arrays: bool,
+    uses_strings: bool,
+
+    // string literal -> the global array holding it, so repeated literals share one copy
+    string_globals: HashMap<String, String>,
+
+    // lambdas still waiting for their own ir function, those get made once main is done
+    pending_lambdas: Vec<PendingLambda>,
+    lambda_count: usize,
+    // how many arguments each method reference takes, one _boundMethodN per count
+    bound_method_arities: BTreeSet<usize>,
+}
+
+struct PendingLambda {
+    name: String,
+    lambda: Lambda,
+    // the captured source names and their types, in the order they sit in the closure
+    captures: Vec<(String, ast::Type)>,
 }
 
 struct ClassMetadata {
@@ -32,9 +97,6 @@ struct ClassMetadata {
     // ex: field name -> index in fieldsA array
     // global array fieldsA: { 2, 0 }
     field_map: HashMap<String, usize>,
-    // ex: method name -> index in vtblA array
-    // global array vtblA: { mA }
-    vtable_map: HashMap<String, usize>,
 }
 
 /*
@@ -86,53 +148,169 @@ impl IRBuilder {
             class_metadata_map: HashMap::new(),
             global_field_ids: HashMap::new(),
             global_method_ids: HashMap::new(),
-            current_block_has_explicit_return: false,
+            current_block_has_explicit_transfer: false,
             type_environment: HashMap::new(),
             classes: vec![],
+            interfaces: vec![],
+            enums: vec![],
+            top_level_functions: vec![],
+            scopes: vec![],
+            loop_labels: vec![],
+            uses_exceptions: false,
+            handlers: vec![],
+            unwind_label: None,
             var_types: HashMap::new(),
+            uses_arrays: false,
+            uses_strings: false,
+            string_globals: HashMap::new(),
+            pending_lambdas: vec![],
+            lambda_count: 0,
+            bound_method_arities: BTreeSet::new(),
         }
     }
 
     fn evaluate_type(&self, expr: &Expression) -> ast::Type {
-        match expr {
-            Expression::Variable(name) => self.type_environment.get(name).unwrap().clone(),
+        match &expr.kind {
+            ExpressionKind::Variable(name) => match self.type_environment.get(&self.resolve(name)) {
+                Some(typ) => typ.clone(),
+                // a top level function used as a value
+                None => self.top_level_functions.iter()
+                    .find(|f| f.name == *name)
+                    .map(|f| function_type(&f.signature()))
+                    .unwrap_or_else(|| panic!("Variable {} not found", name)),
+            },
 
-            Expression::ThisExpr => self.type_environment.get("this").unwrap().clone(),
+            // inside a lambda this is whatever it captured
+            ExpressionKind::ThisExpr => self.type_environment.get(&self.resolve("this")).unwrap().clone(),
 
-            Expression::ClassRef(name) => ast::Type::ClassType(name.clone()),
+            ExpressionKind::Lambda(lambda) => {
+                let args = lambda.args.iter().map(|arg| arg.typ.clone()).collect();
+                ast::Type::Function(args, Box::new(lambda.return_type.clone()))
+            }
 
-            Expression::Null(name) => ast::Type::ClassType(name.clone()),
+            ExpressionKind::MethodRef { base, method_name } => {
+                function_type(&self.find_method(&self.class_of(base), method_name))
+            }
+
+            ExpressionKind::ClassRef { class_name, .. } => ast::Type::ClassType(class_name.clone()),
+
+            ExpressionKind::Variant { enum_name, .. } => ast::Type::ClassType(enum_name.clone()),
+
+            ExpressionKind::Null(typ) => typ.clone(),
 
-            // constants & binoips are always ints
-            Expression::Constant(_) => ast::Type::Int,
+            ExpressionKind::Constant(_) => ast::Type::Int,
+
+            ExpressionKind::Bool(_) => ast::Type::Bool,
+
+            ExpressionKind::StringLiteral(_) => ast::Type::Str,
+
+            // comparisons and and/or are bools, the rest of the binops are ints
+            ExpressionKind::Binop { op, .. } => match op {
+                Operator::Equals | Operator::NotEquals
+                | Operator::LessThan | Operator::GreaterThan
+                | Operator::LessEqual | Operator::GreaterEqual
+                | Operator::And | Operator::Or => ast::Type::Bool,
+                _ => ast::Type::Int,
+            },
 
-            Expression::Binop { .. } => ast::Type::Int,
+            ExpressionKind::Unary { op: UnaryOperator::Not, .. } => ast::Type::Bool,
+
+            ExpressionKind::Unary { .. } => ast::Type::Int,
+
+            ExpressionKind::ArrayAlloc { element, .. } => ast::Type::Array(Box::new(element.clone())),
+
+            // a narrowed int[]? or str? still has its nullable type here
+            ExpressionKind::Index { array, .. } => match strip_nullable(self.evaluate_type(array)) {
+                ast::Type::Array(element) => *element,
+                ast::Type::Str => ast::Type::Int,
+                _ => panic!("index into non-array"),
+            },
+
+            ExpressionKind::Length(_) => ast::Type::Int,
 
             // we can recursively eval the type in a field read by evaluate the base and then 
             // find the type
-            Expression::FieldRead { base, field_name } => {
-                if let ast::Type::ClassType(class_name) = self.evaluate_type(base) {
-                    let class = self.classes.iter().find(|c| c.name == class_name).unwrap();
-                    class.fields.iter()
-                        .find(|(n, _)| n == field_name)
-                        .map(|(_, t)| t.clone())
-                        .unwrap()
-                } else { panic!("field read on int") }
+            ExpressionKind::FieldRead { base, field_name } => {
+                self.ancestors(&self.class_of(base)).into_iter()
+                    .find_map(|class| class.fields.iter().find(|f| f.name == *field_name))
+                    .map(|f| f.typ.clone())
+                    .unwrap()
             }
 
-            Expression::FieldWrite { base, .. } => {
+            ExpressionKind::FieldWrite { base, .. } => {
                 self.evaluate_type(base)
             }
             
             // method call is like field read
-            Expression::MethodCall { base, method_name, .. } => {
-                if let ast::Type::ClassType(class_name) = self.evaluate_type(base) {
-                    let class = self.classes.iter().find(|c| c.name == class_name).unwrap();
-                    let method = class.methods.iter().find(|m| m.name == *method_name).unwrap();
-                    method.return_type.clone()
-                } else { panic!("method call on int") }
+            ExpressionKind::MethodCall { base, method_name, .. } => {
+                self.find_method(&self.class_of(base), method_name).return_type
             }
+
+            ExpressionKind::FunctionCall { function_name, .. } if self.calls_closure(function_name) => {
+                match self.type_environment.get(&self.resolve(function_name)) {
+                    Some(ast::Type::Function(_, ret)) => *ret.clone(),
+                    _ => unreachable!(),
+                }
+            }
+
+            ExpressionKind::FunctionCall { function_name, .. } => {
+                self.top_level_functions.iter()
+                    .find(|f| f.name == *function_name)
+                    .map(|f| f.return_type.clone())
+                    .unwrap_or_else(|| panic!("Function {} not found", function_name))
+            }
+        }
+    }
+
+    // the signature of a method on a class (found through its superclasses) or an interface
+    fn find_method(&self, class_name: &str, method_name: &str) -> ast::MethodSignature {
+        if let Some(interface) = self.interfaces.iter().find(|i| i.name == class_name) {
+            return interface.methods.iter()
+                .find(|m| m.name == method_name)
+                .cloned()
+                .unwrap();
+        }
+        self.ancestors(class_name).into_iter()
+            .find_map(|class| class.methods.iter().find(|m| m.name == method_name))
+            .map(|m| m.signature())
+            .unwrap()
+    }
+
+    // f(args) goes through the function value in a variable f when there is one, same as the type checker
+    fn calls_closure(&self, name: &str) -> bool {
+        matches!(self.type_environment.get(&self.resolve(name)), Some(ast::Type::Function(..)))
+    }
+
+    // the class (or interface) of an object being used, a variable declared A? that the type checker
+    // narrowed with a null test still has its A? type here
+    fn class_of(&self, expr: &Expression) -> String {
+        match self.evaluate_type(expr) {
+            ast::Type::ClassType(name) => name,
+            ast::Type::Nullable(inner) => match *inner {
+                ast::Type::ClassType(name) => name,
+                other => panic!("{}? is not an object", other),
+            },
+            other => panic!("{} is not an object", other),
+        }
+    }
+
+    // an int[]? or str? still gets a null check when it is indexed or its length is read,
+    // even one the type checker narrowed, since its type here is still the nullable one
+    fn is_nullable(&self, expr: &Expression) -> bool {
+        matches!(self.evaluate_type(expr), ast::Type::Nullable(_))
+    }
+
+    // the class and then every superclass above it, the type checker already ruled out cycles
+    fn ancestors(&self, class_name: &str) -> Vec<&ast::Class> {
+        let mut chain = vec![];
+        let mut current = Some(class_name);
+        while let Some(name) = current {
+            let class = self.classes.iter().find(|c| c.name == name)
+                .unwrap_or_else(|| panic!("Class {} not found", name));
+            chain.push(class);
+            current = class.superclass.as_ref().map(|(superclass, _)| superclass.as_str());
         }
+        chain
     }
 
     fn gen_class_metadata(&mut self, program: &ast::Program) {
@@ -196,9 +374,9 @@ impl IRBuilder {
 
         // THIS IS WHAT ALOWS THE POLYMORPHISM
         for class in &program.classes {
-            for (field, _) in &class.fields {
-                if !self.global_field_ids.contains_key(field) {
-                    self.global_field_ids.insert(field.clone(), next_field_id);
+            for field in &class.fields {
+                if !self.global_field_ids.contains_key(&field.name) {
+                    self.global_field_ids.insert(field.name.clone(), next_field_id);
                     next_field_id += 1;
                 }
             }
@@ -211,22 +389,50 @@ impl IRBuilder {
             }
         }
 
+        /*
+        interfaces dont need anything extra at runtime. every vtable already has a slot for every
+        method name in the program, so calling area() through a Shape just loads the object's own
+        vtable and reads the area slot, same as a normal call. a class that implements Shape is
+        guaranteed by the type checker to have filled that slot in
+        the names still get ids here so a call through an interface nobody implements has a slot to read
+        */
+        for interface in &program.interfaces {
+            for method in &interface.methods {
+                if !self.global_method_ids.contains_key(&method.name) {
+                    self.global_method_ids.insert(method.name.clone(), next_method_id);
+                    next_method_id += 1;
+                }
+            }
+        }
+
         let total_fields = self.global_field_ids.len();
         let total_methods = self.global_method_ids.len();
 
         // second pass : build each class's vtable and field offset arrays globally
         //               and then store than metadata for codegen
+        /*
+        with inheritance the superclass's fields come first, so every field of A sits at the
+        same slot in a B and code compiled against A can read a B without knowing it:
+
+            class A [ fields x ]                A object:  [vtable, x]
+            class B extends A [ fields y ]      B object:  [vtable, x, y]
+
+        the vtable starts as a copy of the superclass's and then the subclass's own methods
+        are written over it, so an override takes the same global method id
+        */
         for class in &program.classes {
+            // root class first so the superclass fields and methods get laid down before ours
+            let mut chain = self.ancestors(&class.name);
+            chain.reverse();
+
             // field_name -> slot offset within object
             let mut field_map = HashMap::new();
-            for (i, (field, _)) in class.fields.iter().enumerate() {
-                field_map.insert(field.clone(), 1 + i);
-            }
-
-            // method name -> index within the class's method list
-            let mut vtable_map = HashMap::new();
-            for (i, method) in class.methods.iter().enumerate() {
-                vtable_map.insert(method.name.clone(), i);
+            let mut field_count = 0;
+            for ancestor in &chain {
+                for field in &ancestor.fields {
+                    field_count += 1;
+                    field_map.insert(field.name.clone(), field_count);
+                }
             }
 
             // size = total_methods across all classes
@@ -234,9 +440,11 @@ impl IRBuilder {
             // for methods this class does implement, stor the ir func name
             // which the urntime will evaluate as a function pointer
             let mut vtable_vals: Vec<String> = vec!["0".to_string(); total_methods];
-            for method in &class.methods {
-                let global_id = *self.global_method_ids.get(&method.name).unwrap();
-                vtable_vals[global_id] = format!("{}{}", method.name, class.name);
+            for ancestor in &chain {
+                for method in &ancestor.methods {
+                    let global_id = *self.global_method_ids.get(&method.name).unwrap();
+                    vtable_vals[global_id] = format!("{}{}", method.name, ancestor.name);
+                }
             }
 
             self.globals.push(GlobalArray { 
@@ -259,9 +467,8 @@ impl IRBuilder {
             });
 
             let metadata = ClassMetadata {
-                field_count: class.fields.len(),
+                field_count,
                 field_map,
-                vtable_map,
             };
 
             self.class_metadata_map.insert(class.name.clone(), metadata);
@@ -274,12 +481,229 @@ impl IRBuilder {
         name
     }
 
+    // a fresh ir name for a source variable, the _ keeps it from clashing with anything the program declares
+    fn gen_local_variable(&mut self, name: &str) -> String {
+        let name = format!("{}_{}", name, self.temp_counter);
+        self.temp_counter += 1;
+        name
+    }
+
     fn gen_unique_label(&mut self, prefix: &str) -> String {
         let label = format!("{}{}", prefix, self.block_counter);
         self.block_counter += 1;
         label
     }
 
+    /*
+    a call to a method, constructor, or function from the program. when the program throws, the callee
+    also gets the exception state, and whether it came back with an exception pending gets checked:
+
+        %callResult = call(f, %this, %exceptionState, args...)
+        %pending = load(%exceptionState)
+        if %pending then <innermost catch, or unwind> else noThrow
+    noThrow:
+        continue
+    */
+    fn gen_call(&mut self, dest: String, func: Value, receiver: Value, mut args: Vec<Value>) {
+        if !self.uses_exceptions {
+            self.push_instruction(Primitive::Call { dest, func, receiver, args });
+            return;
+        }
+
+        args.insert(0, var(EXCEPTION_STATE));
+        self.push_instruction(Primitive::Call { dest, func, receiver, args });
+
+        let pending = self.gen_unique_variable("pending");
+        self.push_instruction(Primitive::Load {
+            dest: pending.clone(),
+            addr: var(EXCEPTION_STATE),
+        });
+        let handler = self.current_handler();
+        let ok_label = self.gen_unique_label("noThrow");
+        self.finish_block(
+            ControlTransfer::Branch {
+                cond: Value::Variable(pending),
+                then_lab: handler,
+                else_lab: ok_label.clone(),
+            },
+            ok_label,
+        );
+    }
+
+    // a closure object with the code to run in slot 0 and the values it carries after it
+    fn gen_closure(&mut self, code: String, values: Vec<Value>) -> String {
+        let closure = self.gen_unique_variable("closure");
+        self.push_instruction(Primitive::Alloc {
+            dest: closure.clone(),
+            size: 1 + values.len() as i64,
+        });
+        self.push_instruction(Primitive::Store {
+            addr: var(&closure),
+            val: Value::Code(code),
+        });
+        for (i, val) in values.into_iter().enumerate() {
+            self.push_instruction(Primitive::SetElt {
+                arr: var(&closure),
+                idx: Value::Constant(1 + i as i64),
+                val,
+            });
+        }
+        closure
+    }
+
+    // where an exception goes from here, the catches of the innermost try or else back to the caller
+    fn current_handler(&mut self) -> String {
+        if let Some(handler) = self.handlers.last() {
+            return handler.clone();
+        }
+        if self.unwind_label.is_none() {
+            self.unwind_label = Some(self.gen_unique_label("unwind"));
+        }
+        self.unwind_label.clone().unwrap()
+    }
+
+    /*
+    and/or only evaluate the right side when the left side doesnt already decide the answer,
+    so they become control flow instead of a binop. the result is always 0 or 1
+
+        current_block:
+            %scResult = 0           (1 for or)
+            branch left -> scRight, scMerge     (scMerge, scRight for or)
+
+        scRight:
+            right
+            %scResult = right != 0
+            jump -> scMerge
+
+        scMerge:
+            continue
+
+    scResult is assigned on both paths so ssa puts a phi for it at the top of scMerge
+    */
+    fn gen_short_circuit(&mut self, lhs: &Expression, op: &Operator, rhs: &Expression) -> Value {
+        let left = self.gen_expression(lhs);
+        let cond_var = match &left {
+            Value::Variable(v) => v.clone(),
+            other => {
+                let tmp = self.gen_unique_variable("cond");
+                self.push_instruction(Primitive::Assign {
+                    dest: tmp.clone(),
+                    value: other.clone(),
+                });
+                tmp
+            }
+        };
+
+        let result = self.gen_unique_variable("scResult");
+        self.var_types.insert(result.clone(), ast::Type::Bool);
+        let decided = if *op == Operator::Or { 1 } else { 0 };
+        self.push_instruction(Primitive::Assign {
+            dest: result.clone(),
+            value: Value::Constant(decided),
+        });
+
+        let right_label = self.gen_unique_label("scRight");
+        let merge_label = self.gen_unique_label("scMerge");
+        let (then_lab, else_lab) = if *op == Operator::Or {
+            (merge_label.clone(), right_label.clone())
+        } else {
+            (right_label.clone(), merge_label.clone())
+        };
+        self.finish_block(
+            ControlTransfer::Branch {
+                cond: Value::Variable(cond_var),
+                then_lab,
+                else_lab,
+            },
+            right_label,
+        );
+
+        // the right side is a bool so it is already 0 or 1
+        let right = self.gen_expression(rhs);
+        self.push_instruction(Primitive::Assign {
+            dest: result.clone(),
+            value: right,
+        });
+        self.finish_block(ControlTransfer::Jump { target: merge_label.clone() }, merge_label);
+
+        Value::Variable(result)
+    }
+
+    // gen_binop types its temps as ints, this fixes that up when the expression was a bool
+    // so the cfg passes know the temp can only ever be 0 or 1
+    fn mark_bool(&mut self, expression: &Expression, result: &Value) {
+        if let Value::Variable(name) = result
+            && self.evaluate_type(expression) == ast::Type::Bool {
+            self.var_types.insert(name.clone(), ast::Type::Bool);
+        }
+    }
+
+    // dest = lhs op rhs into a fresh int temp, returns the temp
+    fn gen_binop(&mut self, prefix: &str, lhs: Value, op: &str, rhs: Value) -> Value {
+        let result = self.gen_unique_variable(prefix);
+        self.var_types.insert(result.clone(), ast::Type::Int);
+        self.push_instruction(Primitive::BinOp {
+            dest: result.clone(),
+            lhs,
+            op: op.to_string(),
+            rhs,
+        });
+        Value::Variable(result)
+    }
+
+    /*
+    the ir compares unsigned, flipping the sign bit of both sides first makes that a signed compare
+    (the most negative int becomes 0 and the biggest one becomes all ones)
+
+        %left = a ^ MIN
+        %right = b ^ MIN
+        %result = %left < %right
+    */
+    fn gen_signed_compare(&mut self, lhs: Value, op: &str, rhs: Value) -> Value {
+        let left = self.gen_binop("left", lhs, "^", Value::Constant(i64::MIN));
+        let right = self.gen_binop("right", rhs, "^", Value::Constant(i64::MIN));
+        self.gen_binop("result", left, op, right)
+    }
+
+    // 0 when x is positive and all ones when it's negative, so (x ^ mask) - mask is |x|
+    fn gen_sign_mask(&mut self, x: Value) -> Value {
+        let sign = self.gen_binop("sign", x, ">>", Value::Constant(63));
+        self.gen_binop("mask", Value::Constant(0), "-", sign)
+    }
+
+    /*
+    a / b rounding toward zero: divide the sizes unsigned, then negate when exactly one side was negative
+
+        %aMask = 0 - (a >> 63)
+        %aAbs = (a ^ %aMask) - %aMask
+        (the same for b)
+        %quotient = %aAbs / %bAbs
+        %qMask = %aMask ^ %bMask
+        %result = (%quotient ^ %qMask) - %qMask
+    */
+    fn gen_signed_divide(&mut self, lhs: Value, rhs: Value) -> Value {
+        let lhs_mask = self.gen_sign_mask(lhs.clone());
+        let lhs_flipped = self.gen_binop("flipped", lhs, "^", lhs_mask.clone());
+        let lhs_abs = self.gen_binop("abs", lhs_flipped, "-", lhs_mask.clone());
+        let rhs_mask = self.gen_sign_mask(rhs.clone());
+        let rhs_flipped = self.gen_binop("flipped", rhs, "^", rhs_mask.clone());
+        let rhs_abs = self.gen_binop("abs", rhs_flipped, "-", rhs_mask.clone());
+
+        let quotient = self.gen_binop("quotient", lhs_abs, "/", rhs_abs);
+        let quotient_mask = self.gen_binop("mask", lhs_mask, "^", rhs_mask);
+        let flipped = self.gen_binop("flipped", quotient, "^", quotient_mask.clone());
+        self.gen_binop("result", flipped, "-", quotient_mask)
+    }
+
+    // a >> n keeping the sign: a negative a is complemented, shifted, and complemented back
+    // so the bits coming in on the left are ones
+    fn gen_signed_shift(&mut self, lhs: Value, rhs: Value) -> Value {
+        let mask = self.gen_sign_mask(lhs.clone());
+        let flipped = self.gen_binop("flipped", lhs, "^", mask.clone());
+        let shifted = self.gen_binop("shifted", flipped, ">>", rhs);
+        self.gen_binop("result", shifted, "^", mask)
+    }
+
     fn push_instruction(&mut self, primitive: Primitive) {
         self.current_block.primitives.push(primitive);
     }
@@ -296,7 +720,7 @@ impl IRBuilder {
                 val: Value::Constant(0),
             },
         };
-        self.current_block_has_explicit_return = false;
+        self.current_block_has_explicit_transfer = false;
     }
 
     // in this refactor, we will be grouping basic blocks into functions
@@ -310,6 +734,14 @@ impl IRBuilder {
         }
         self.current_function_blocks.push(self.current_block.clone());
 
+        if let Some(label) = self.unwind_label.take() {
+            if name == "main" {
+                self.gen_uncaught(&label);
+            } else {
+                let transfer = ControlTransfer::Return { val: Value::Constant(0) };
+                self.current_function_blocks.push(runtime_block(&label, vec![], transfer));
+            }
+        }
 
         self.functions.push(Function {
             name,
@@ -323,86 +755,244 @@ impl IRBuilder {
             primitives: vec![],
             control_transfer: ControlTransfer::Return { val: Value::Constant(0) }
         };
-        self.current_block_has_explicit_return = false;
+        self.current_block_has_explicit_transfer = false;
+    }
+
+    /*
+    nobody above main to pass an exception to, so it prints the name of the thrown object's class
+    (one character code per line, like a str) and fails. the ir can only fail with a fixed set of
+    reasons, and NoSuchMethod is the one nothing else uses since the type checker rules it out
+
+        unwind:
+            %uncaught = load(%exceptionState)
+            %uncaughtVtable = load(%uncaught)
+            %isA = %uncaughtVtable == @vtblA
+            if %isA then uncaughtA else uncaughtTest
+        uncaughtA:
+            %printed = call(_printStr, 0, @str3)        # "A"
+            fail NoSuchMethod
+        uncaughtTest:
+            ...                                         # the same for every other class
+    */
+    fn gen_uncaught(&mut self, label: &str) {
+        self.uses_strings = true;
+        let fail = || ControlTransfer::Fail { message: "NoSuchMethod".to_string() };
+
+        let uncaught = self.gen_unique_variable("uncaught");
+        let vtable = self.gen_unique_variable("uncaughtVtable");
+        let mut primitives = vec![
+            Primitive::Load { dest: uncaught.clone(), addr: var(EXCEPTION_STATE) },
+            Primitive::Load { dest: vtable.clone(), addr: var(&uncaught) },
+        ];
+        let mut test_label = label.to_string();
+
+        let classes: Vec<String> = self.classes.iter().map(|class| class.name.clone()).collect();
+        for class in classes {
+            let is_class = self.gen_unique_variable("isClass");
+            let print_label = self.gen_unique_label("uncaught");
+            let next_label = self.gen_unique_label("uncaughtTest");
+            primitives.push(binop(&is_class, var(&vtable), "==", Value::Global(format!("vtbl{}", class))));
+            self.current_function_blocks.push(runtime_block(&test_label, primitives, ControlTransfer::Branch {
+                cond: var(&is_class),
+                then_lab: print_label.clone(),
+                else_lab: next_label.clone(),
+            }));
+
+            let name = self.gen_string_global(&class);
+            let printed = self.gen_unique_variable("printed");
+            self.current_function_blocks.push(runtime_block(&print_label, vec![Primitive::Call {
+                dest: printed,
+                func: Value::Code(PRINT_STR_FUNCTION.to_string()),
+                receiver: Value::Constant(0),
+                args: vec![Value::Global(name)],
+            }], fail()));
+
+            primitives = vec![];
+            test_label = next_label;
+        }
+        self.current_function_blocks.push(runtime_block(&test_label, primitives, fail()));
     }
 
     // need to return value for generation of nested expressions and statements
     fn gen_expression(&mut self, expression: &Expression) -> Value {
-        match expression {
+        match &expression.kind {
 
             // if its a contant, tag the leftmost bit with 1
-            Expression::Constant(n) => {
+            ExpressionKind::Constant(n) => {
                 Value::Constant(*n)
             }
 
-            Expression::Variable(name) => {
-                Value::Variable(name.clone())
+            // bools are just 1 and 0
+            ExpressionKind::Bool(b) => {
+                Value::Constant(*b as i64)
+            }
+
+            ExpressionKind::Variable(name) => {
+                let ir_name = self.resolve(name);
+                if self.type_environment.contains_key(&ir_name) {
+                    return Value::Variable(ir_name);
+                }
+                // a top level function as a value is a closure with nothing captured,
+                // it ignores the closure that comes in as its this
+                let closure = self.gen_closure(format!("{}{}", FUNCTION_PREFIX, name), vec![]);
+                self.var_types.insert(closure.clone(), self.evaluate_type(expression));
+                Value::Variable(closure)
+            }
+
+            ExpressionKind::StringLiteral(text) => {
+                Value::Global(self.gen_string_global(text))
             }
 
             // we no longer need to do type checking so just do raw math
-            Expression::Binop { lhs, op, rhs } => {
+            // the ir only has + - * / & | ^ == < > << >>, everything else is built out of those
+            //      a != b  ->  (a == b) ^ 1
+            //      a <= b  ->  (a > b) ^ 1
+            //      a >= b  ->  (a < b) ^ 1
+            //      a % b   ->  a - (a / b) * b
+            // the ir's ints are unsigned but source ints are signed, so < > / and >> go through
+            // gen_signed_compare, gen_signed_divide and gen_signed_shift
+            ExpressionKind::Binop { lhs, op: op @ (Operator::And | Operator::Or), rhs } => {
+                self.gen_short_circuit(lhs, op, rhs)
+            }
+
+            ExpressionKind::Binop { lhs, op, rhs } => {
+                let compares_strings = strip_nullable(self.evaluate_type(lhs)) == ast::Type::Str;
                 let left = self.gen_expression(lhs);
                 let right = self.gen_expression(rhs);
 
-                if *op == Operator::Equals {
-                    let result = self.gen_unique_variable("result");
-                    self.var_types.insert(result.clone(), ast::Type::Int);
+                let result = match op {
+                    // strings compare by their characters, not by address
+                    Operator::Equals if compares_strings => self.gen_str_equals(left, right),
+                    Operator::NotEquals if compares_strings => {
+                        let eq_result = self.gen_str_equals(left, right);
+                        self.gen_binop("result", eq_result, "^", Value::Constant(1))
+                    }
+                    Operator::NotEquals => {
+                        let eq_result = self.gen_binop("eqResult", left, "==", right);
+                        self.gen_binop("result", eq_result, "^", Value::Constant(1))
+                    }
+                    Operator::LessThan => self.gen_signed_compare(left, "<", right),
+                    Operator::GreaterThan => self.gen_signed_compare(left, ">", right),
+                    Operator::LessEqual => {
+                        let gt_result = self.gen_signed_compare(left, ">", right);
+                        self.gen_binop("result", gt_result, "^", Value::Constant(1))
+                    }
+                    Operator::GreaterEqual => {
+                        let lt_result = self.gen_signed_compare(left, "<", right);
+                        self.gen_binop("result", lt_result, "^", Value::Constant(1))
+                    }
+                    Operator::Divide => self.gen_signed_divide(left, right),
+                    Operator::Modulo => {
+                        let quotient = self.gen_signed_divide(left.clone(), right.clone());
+                        let product = self.gen_binop("product", quotient, "*", right);
+                        self.gen_binop("result", left, "-", product)
+                    }
+                    Operator::ShiftRight => self.gen_signed_shift(left, right),
+                    _ => self.gen_binop("result", left, &op.to_string(), right),
+                };
+                self.mark_bool(expression, &result);
+                result
+            }
 
-                    self.push_instruction(Primitive::BinOp {
-                        dest: result.clone(),
-                        lhs: left,
-                        op: "==".to_string(),
-                        rhs: right,
-                    });
-                    return Value::Variable(result);
-                }
+            // the ir has no unary ops so these all become a binop with a constant
+            //      -x  ->  0 - x
+            //      !x  ->  x ^ 1      (x is a bool so only the low bit is set)
+            //      ~x  ->  x ^ -1     (all ones)
+            // constant folding takes care of them when x is known
+            ExpressionKind::Unary { op, operand } => {
+                let operand = self.gen_expression(operand);
+
+                let (lhs, op, rhs) = match op {
+                    UnaryOperator::Negate => (Value::Constant(0), "-", operand),
+                    UnaryOperator::Not => (operand, "^", Value::Constant(1)),
+                    UnaryOperator::Complement => (operand, "^", Value::Constant(-1)),
+                };
+                let result = self.gen_binop("result", lhs, op, rhs);
+                self.mark_bool(expression, &result);
+                result
+            }
 
-                if *op == Operator::NotEquals {
-                    let eq_result = self.gen_unique_variable("eqResult");
-                    self.var_types.insert(eq_result.clone(), ast::Type::Int);
-                    self.push_instruction(Primitive::BinOp {
-                        dest: eq_result.clone(),
-                        lhs: left,
-                        op: "==".to_string(),
-                        rhs: right,
-                    });
-                    let result = self.gen_unique_variable("result");
-                    self.var_types.insert(result.clone(), ast::Type::Int);
+            ExpressionKind::ThisExpr => {
+                Value::Variable(self.resolve("this"))
+            }
 
-                    self.push_instruction(Primitive::BinOp {
-                        dest: result.clone(),
-                        lhs: Value::Variable(eq_result),
-                        op: "^".to_string(),
-                        rhs: Value::Constant(1),
-                    });
-                    return Value::Variable(result);
-                }
+            /*
+            a lambda is a closure object, its code and then a copy of everything it captures:
 
-                let result = self.gen_unique_variable("result");
-                self.var_types.insert(result.clone(), ast::Type::Int);
-                self.push_instruction(Primitive::BinOp {
-                    dest: result.clone(),
-                    lhs: left,
-                    op: op.to_string(),
-                    rhs: right,
-                });
-                Value::Variable(result)
+                # function(x:int): { return x + k }
+                %closure0 = alloc(2)
+                store(%closure0, _lambda0)
+                setelt(%closure0, 1, %k)
+
+            the body becomes its own function _lambda0(this, x), this being the closure,
+            that starts by reading k back out of it
+            */
+            ExpressionKind::Lambda(lambda) => {
+                let captured: Vec<(String, ast::Type)> = captures(lambda).into_iter()
+                    .filter_map(|capture| {
+                        let typ = self.type_environment.get(&self.resolve(&capture.name))?.clone();
+                        Some((capture.name, typ))
+                    })
+                    .collect();
+                let name = format!("{}{}", LAMBDA_PREFIX, self.lambda_count);
+                self.lambda_count += 1;
+
+                let values = captured.iter().map(|(name, _)| Value::Variable(self.resolve(name))).collect();
+                let closure = self.gen_closure(name.clone(), values);
+                self.var_types.insert(closure.clone(), self.evaluate_type(expression));
+                self.pending_lambdas.push(PendingLambda { name, lambda: lambda.clone(), captures: captured });
+                Value::Variable(closure)
             }
 
-            Expression::ThisExpr => {
-                Value::Variable("this".to_string())
+            /*
+            ^obj.m looks the method up in obj's vtable right away, and keeps both:
+
+                %closure0 = alloc(3)
+                store(%closure0, _boundMethod1)
+                setelt(%closure0, 1, %obj)
+                setelt(%closure0, 2, %methodPtr)
+            */
+            ExpressionKind::MethodRef { base, method_name } => {
+                let typ = self.evaluate_type(expression);
+                let object = self.gen_expression(base);
+
+                let vtable = self.gen_unique_variable("vtable");
+                self.push_instruction(Primitive::Load {
+                    dest: vtable.clone(),
+                    addr: object.clone(),
+                });
+                let global_method_id = *self.global_method_ids.get(method_name)
+                    .unwrap_or_else(|| panic!("Method {} not found", method_name));
+                let method_ptr = self.gen_unique_variable("methodPtr");
+                self.push_instruction(Primitive::GetElt {
+                    dest: method_ptr.clone(),
+                    arr: Value::Variable(vtable),
+                    idx: Value::Constant(global_method_id as i64),
+                });
+
+                let ast::Type::Function(args, _) = &typ else { unreachable!() };
+                self.bound_method_arities.insert(args.len());
+                let closure = self.gen_closure(format!("{}{}", BOUND_METHOD_PREFIX, args.len()),
+                    vec![object, Value::Variable(method_ptr)]);
+                self.var_types.insert(closure.clone(), typ);
+                Value::Variable(closure)
             }
 
-            Expression::ClassRef(class_name) => {
+            ExpressionKind::ClassRef { class_name, args, .. } => {
                 /*
                     # x = new A
                     %x0 = alloc(3)    # vtable, field map, field x
                     store(%x0, @vtblA)
                     %1 = %x0 + 8
                     store(%1, @fieldsA)
+
+                    # x = @A(5), when A has a constructor the args go to a direct call after that
+                    %2 = call(constructorA, %x0, 5)
                 */
+                let arg_values: Vec<Value> = args.iter().map(|arg| self.gen_expression(arg)).collect();
+
                 let metadata = self.class_metadata_map.get(class_name)
-                    .expect(&format!("Class {} not found", class_name));
+                    .unwrap_or_else(|| panic!("Class {} not found", class_name));
 
                 let alloc_size = 1 + metadata.field_count as i64;
                 let obj_addr = self.gen_unique_variable("objAddr");
@@ -419,6 +1009,55 @@ impl IRBuilder {
                     val: Value::Global(format!("vtbl{}", class_name)),
                 });
 
+                // constructors arent in the vtable, the class is known right here so call it by name
+                let has_constructor = self.classes.iter()
+                    .any(|class| class.name == *class_name && class.constructor.is_some());
+                if has_constructor {
+                    let result = self.gen_unique_variable("constructorResult");
+                    self.var_types.insert(result.clone(), ast::Type::Int);
+                    self.gen_call(result, Value::Code(format!("constructor{}", class_name)),
+                        Value::Variable(obj_addr.clone()), arg_values);
+                }
+
+                Value::Variable(obj_addr)
+            }
+
+            /*
+                an enum value is the variant's tag (its index in the enum) followed by its fields,
+                there is no vtable since nothing ever calls a method on one
+
+                    # @Shape.Rect(2, 3), Rect is variant 1
+                    %variant0 = alloc(3)
+                    store(%variant0, 1)
+                    setelt(%variant0, 1, 2)
+                    setelt(%variant0, 2, 3)
+            */
+            ExpressionKind::Variant { enum_name, variant, args } => {
+                let arg_values: Vec<Value> = args.iter().map(|arg| self.gen_expression(arg)).collect();
+                let tag = self.enums.iter()
+                    .find(|declared| declared.name == *enum_name)
+                    .and_then(|declared| declared.variant(variant))
+                    .map(|(tag, _)| tag)
+                    .unwrap_or_else(|| panic!("Variant {}.{} not found", enum_name, variant));
+
+                let obj_addr = self.gen_unique_variable("variant");
+                self.var_types.insert(obj_addr.clone(), ast::Type::ClassType(enum_name.clone()));
+                self.push_instruction(Primitive::Alloc {
+                    dest: obj_addr.clone(),
+                    size: 1 + arg_values.len() as i64,
+                });
+                self.push_instruction(Primitive::Store {
+                    addr: Value::Variable(obj_addr.clone()),
+                    val: Value::Constant(tag as i64),
+                });
+                for (i, val) in arg_values.into_iter().enumerate() {
+                    self.push_instruction(Primitive::SetElt {
+                        arr: Value::Variable(obj_addr.clone()),
+                        idx: Value::Constant(1 + i as i64),
+                        val,
+                    });
+                }
+
                 Value::Variable(obj_addr)
             }
 
@@ -442,40 +1081,20 @@ impl IRBuilder {
                     final: ...
 
                 AFTER (typed, direct slot access):
-                    if %x then fieldOk else badptr
-                    fieldOk:
                     %result = getelt(%x, 1)
-                    jump final
-                    badptr: fail NotAPointer
-                    final: ...
 
-                this is good optimization after type checijgn
+                this is good optimization after type checijgn. there used to be a null check in front,
+                but the type checker only lets a field be used through an A, never an A?
+                (or an A? variable after a null test), so x cant be null here
             */
-            Expression::FieldRead { base, field_name } => {
+            ExpressionKind::FieldRead { base, field_name } => {
                 let field_type = self.evaluate_type(expression);
                 let base_val = self.gen_expression(base);
-                
-                let class_name = match self.evaluate_type(base) {
-                    ast::Type::ClassType(n) => n,
-                    _ => panic!("field read on non-class"),
-                };
 
+                let class_name = self.class_of(base);
                 let metadata = self.class_metadata_map.get(&class_name).unwrap();
                 let slot = *metadata.field_map.get(field_name).unwrap();
 
-                let bad_ptr = self.gen_unique_label("badptr");
-                let ok_label = self.gen_unique_label("fieldOk");
-                let final_label = self.gen_unique_label("final");
-
-                self.finish_block(
-                    ControlTransfer::Branch {
-                        cond: base_val.clone(),
-                        then_lab: ok_label.clone(),
-                        else_lab: bad_ptr.clone(),
-                    },
-                    ok_label.clone(),
-                );
-
                 let result = self.gen_unique_variable("result");
                 self.var_types.insert(result.clone(), field_type.clone());
 
@@ -485,82 +1104,35 @@ impl IRBuilder {
                     idx: Value::Constant(slot as i64),
                 });
 
-                self.finish_block(
-                    ControlTransfer::Jump { target: final_label.clone() },
-                    bad_ptr,
-                );
-                self.finish_block(
-                    ControlTransfer::Fail { message: "NotAPointer".to_string() },
-                    final_label,
-                );
-
                 Value::Variable(result)
             }
 
             // similar opt here much shorter code no untagging, etc.
-            Expression::FieldWrite { base, field_name, value } => {
+            ExpressionKind::FieldWrite { base, field_name, value } => {
                 let base_val = self.gen_expression(base);
                 let val = self.gen_expression(value);
 
-                let class_name = match self.evaluate_type(base) {
-                    ast::Type::ClassType(n) => n,
-                    _ => panic!("field write on non-class"),
-                };
+                let class_name = self.class_of(base);
                 let metadata = self.class_metadata_map.get(&class_name).unwrap();
                 let slot = *metadata.field_map.get(field_name).unwrap();
 
-                let bad_ptr = self.gen_unique_label("badptr");
-                let ok_label = self.gen_unique_label("fieldOk");
-                let final_label = self.gen_unique_label("final");
-
-                self.finish_block(
-                    ControlTransfer::Branch {
-                        cond: base_val.clone(),
-                        then_lab: ok_label.clone(),
-                        else_lab: bad_ptr.clone(),
-                    },
-                    ok_label.clone(),
-                );
-
                 self.push_instruction(Primitive::SetElt {
                     arr: base_val,
                     idx: Value::Constant(slot as i64),
                     val,
                 });
 
-                self.finish_block(
-                    ControlTransfer::Jump { target: final_label.clone() },
-                    bad_ptr,
-                );
-                self.finish_block(
-                    ControlTransfer::Fail { message: "NotAPointer".to_string() },
-                    final_label,
-                );
-
                 Value::Constant(0)
             }
 
             /*
             BEFORE: %tag = %x & 1 --> if %tag --> load vtable --> getelt --> if %methodPtr --> call
-            AFTER:  if %x then ok else badptr --> load vtable --> getelt --> call (no method check)
+            AFTER:  load vtable --> getelt --> call (no method check, and no null check since x cant be null)
             */
-            Expression::MethodCall { base, method_name, args } => {
+            ExpressionKind::MethodCall { base, method_name, args } => {
                 let return_type = self.evaluate_type(expression);
                 let base = self.gen_expression(base);
 
-                let badptr = self.gen_unique_label("badptr");
-                let ok_label = self.gen_unique_label("methodOk");
-                let final_label = self.gen_unique_label("final");
-
-                self.finish_block(
-                    ControlTransfer::Branch {
-                        cond: base.clone(),
-                        then_lab: ok_label.clone(),
-                        else_lab: badptr.clone(),
-                    },
-                    ok_label.clone(),
-                );
-
                 // load vtable
                 let vtable = self.gen_unique_variable("vtable");
                 self.push_instruction(Primitive::Load {
@@ -570,7 +1142,7 @@ impl IRBuilder {
 
                 // lookup method - type checker guarantees it exists, no need to check
                 let global_method_id = *self.global_method_ids.get(method_name)
-                    .expect(&format!("Method {} not found", method_name));
+                    .unwrap_or_else(|| panic!("Method {} not found", method_name));
                 let method_ptr = self.gen_unique_variable("methodPtr");
                 self.push_instruction(Primitive::GetElt {
                     dest: method_ptr.clone(),
@@ -586,66 +1158,509 @@ impl IRBuilder {
                     .map(|a| self.gen_expression(a))
                     .collect();
 
+                self.gen_call(result.clone(), Value::Variable(method_ptr), base, arguments);
+
+                Value::Variable(result)
+            }
+
+            // the code comes out of the closure, which goes in as this
+            //      %code = load(%f)
+            //      %callResult = call(%code, %f, args...)
+            ExpressionKind::FunctionCall { function_name, args } if self.calls_closure(function_name) => {
+                let return_type = self.evaluate_type(expression);
+                let closure = Value::Variable(self.resolve(function_name));
+                let arguments: Vec<Value> = args
+                    .iter()
+                    .map(|a| self.gen_expression(a))
+                    .collect();
+
+                let code = self.gen_unique_variable("code");
+                self.push_instruction(Primitive::Load {
+                    dest: code.clone(),
+                    addr: closure.clone(),
+                });
+                let result = self.gen_unique_variable("callResult");
+                self.var_types.insert(result.clone(), return_type);
+                self.gen_call(result.clone(), Value::Variable(code), closure, arguments);
+
+                Value::Variable(result)
+            }
+
+            // the function is known at compile time, so no null check or vtable, just
+            //      %callResult = call(fn_f, 0, args...)
+            ExpressionKind::FunctionCall { function_name, args } => {
+                let return_type = self.evaluate_type(expression);
+                let arguments: Vec<Value> = args
+                    .iter()
+                    .map(|a| self.gen_expression(a))
+                    .collect();
+
+                let result = self.gen_unique_variable("callResult");
+                self.var_types.insert(result.clone(), return_type);
+                self.gen_call(result.clone(), Value::Code(format!("{}{}", FUNCTION_PREFIX, function_name)),
+                    Value::Constant(0), arguments);
+
+                Value::Variable(result)
+            }
+
+            ExpressionKind::Null(_) => {
+                Value::Constant(0)
+            }
+
+            // %array = call(_allocArray, 0, %n)
+            ExpressionKind::ArrayAlloc { length, .. } => {
+                let array_type = self.evaluate_type(expression);
+                let length = self.gen_expression(length);
+                self.uses_arrays = true;
+
+                let result = self.gen_unique_variable("array");
+                self.var_types.insert(result.clone(), array_type);
                 self.push_instruction(Primitive::Call {
                     dest: result.clone(),
-                    func: Value::Variable(method_ptr),
-                    receiver: base,
-                    args: arguments,
+                    func: Value::Code(ARRAY_ALLOC_FUNCTION.to_string()),
+                    receiver: Value::Constant(0),
+                    args: vec![length],
                 });
+                Value::Variable(result)
+            }
 
-                self.finish_block(
-                    ControlTransfer::Jump { target: final_label.clone() },
-                    badptr,
-                );
-                self.finish_block(
-                    ControlTransfer::Fail { message: "NotAPointer".to_string() },
-                    final_label,
-                );
+            ExpressionKind::Index { array, index } => {
+                let element_type = self.evaluate_type(expression);
+                let nullable = self.is_nullable(array);
+                let array = self.gen_expression(array);
+                let index = self.gen_expression(index);
+                let slot = self.gen_array_slot(&array, index, nullable);
 
+                let result = self.gen_unique_variable("element");
+                self.var_types.insert(result.clone(), element_type);
+                self.push_instruction(Primitive::GetElt {
+                    dest: result.clone(),
+                    arr: array,
+                    idx: slot,
+                });
                 Value::Variable(result)
             }
 
-            Expression::Null(_) => {
-                Value::Constant(0)
+            ExpressionKind::Length(array) => {
+                let nullable = self.is_nullable(array);
+                let array = self.gen_expression(array);
+                self.gen_array_length(&array, nullable)
             }
         }
     }
 
+    /*
+    strings use the same layout as an int[], [length, char 0, char 1, ...], but live in the
+    data section since they never change:
+        global array str0: { 2, 104, 105 }         "hi"
+    */
+    fn gen_string_global(&mut self, text: &str) -> String {
+        if let Some(name) = self.string_globals.get(text) {
+            return name.clone();
+        }
+        let name = format!("str{}", self.string_globals.len());
+        let mut vals = vec![text.chars().count().to_string()];
+        vals.extend(text.chars().map(|c| (c as u32).to_string()));
+        self.globals.push(GlobalArray { name: name.clone(), vals });
+        self.string_globals.insert(text.to_string(), name.clone());
+        name
+    }
+
+    // %result = call(_strEquals, 0, %a, %b)
+    fn gen_str_equals(&mut self, left: Value, right: Value) -> Value {
+        self.uses_strings = true;
+        let result = self.gen_unique_variable("result");
+        self.var_types.insert(result.clone(), ast::Type::Int);
+        self.push_instruction(Primitive::Call {
+            dest: result.clone(),
+            func: Value::Code(STR_EQUALS_FUNCTION.to_string()),
+            receiver: Value::Constant(0),
+            args: vec![left, right],
+        });
+        Value::Variable(result)
+    }
+
+    /*
+    arrays are laid out as [length, element 0, element 1, ...] so element i is in slot i + 1
+
+    reading the length of an int[]? (or a str?) null checks it first:
+            if %array then arrayOk else badptr
+        badptr:
+            fail NotAPointer
+        arrayOk:
+            %length = getelt(%array, 0)
+
+    an int[] can't be null, so like a field read it goes straight to the getelt
+    */
+    fn gen_array_length(&mut self, array: &Value, nullable: bool) -> Value {
+        if nullable {
+            let bad_ptr = self.gen_unique_label("badptr");
+            let ok_label = self.gen_unique_label("arrayOk");
+            self.finish_block(
+                ControlTransfer::Branch {
+                    cond: array.clone(),
+                    then_lab: ok_label.clone(),
+                    else_lab: bad_ptr.clone(),
+                },
+                bad_ptr,
+            );
+            self.finish_block(ControlTransfer::Fail { message: "NotAPointer".to_string() }, ok_label);
+        }
+
+        let length = self.gen_unique_variable("length");
+        self.var_types.insert(length.clone(), ast::Type::Int);
+        self.push_instruction(Primitive::GetElt {
+            dest: length.clone(),
+            arr: array.clone(),
+            idx: Value::Constant(0),
+        });
+        Value::Variable(length)
+    }
+
+    // bounds check an index (null checking an int[]? first) and return the slot it lives in
+    // the comparison is unsigned so a negative index is out of bounds too.
+    // the ir only has a few fail reasons, so out of bounds shows up as NoSuchField
+    fn gen_array_slot(&mut self, array: &Value, index: Value, nullable: bool) -> Value {
+        let length = self.gen_array_length(array, nullable);
+        let in_bounds = self.gen_binop("inBounds", index.clone(), "<", length);
+
+        let out_of_bounds = self.gen_unique_label("outOfBounds");
+        let ok_label = self.gen_unique_label("indexOk");
+        self.finish_block(
+            ControlTransfer::Branch {
+                cond: in_bounds,
+                then_lab: ok_label.clone(),
+                else_lab: out_of_bounds.clone(),
+            },
+            out_of_bounds,
+        );
+        self.finish_block(ControlTransfer::Fail { message: "NoSuchField".to_string() }, ok_label);
+
+        self.gen_binop("slot", index, "+", Value::Constant(1))
+    }
+
+    /*
+    alloc only takes a constant, so arrays go through this helper which rounds the length up to
+    the next power of two and picks that size off a fixed ladder of allocs:
+
+        _allocArray(this, n):
+            %fits = %n < 2
+            if %fits then arrayAlloc else arraySize
+        arrayAlloc:
+            %array = alloc(2)           (1 element + the length slot)
+            setelt(%array, 0, %n)
+            ret %array
+        arraySize:
+            %fits = %n < 3
+            if %fits then arrayAlloc else arraySize
+        ...
+        arrayTooLarge:
+            fail NoSuchField
+
+    at most half of an array is wasted, which is the price of not having a variable sized alloc
+    */
+    /*
+        _printStr(this, s):
+            if %s then printStrStart else printStrNull
+        printStrNull:
+            fail NotAPointer
+        printStrStart:
+            %length = getelt(%s, 0)
+            %i = 0
+            jump printStrCond
+        printStrCond:
+            %more = %i < %length
+            if %more then printStrBody else printStrDone
+        printStrBody:
+            %slot = %i + 1
+            %char = getelt(%s, %slot)
+            print(%char)
+            %i = %slot
+            jump printStrCond
+        printStrDone:
+            ret 0
+    */
+    fn gen_print_str_function(&mut self) {
+        let null_label = self.gen_unique_label("printStrNull");
+        let start_label = self.gen_unique_label("printStrStart");
+        let cond_label = self.gen_unique_label("printStrCond");
+        let body_label = self.gen_unique_label("printStrBody");
+        let done_label = self.gen_unique_label("printStrDone");
+
+        let blocks = vec![
+            runtime_block(PRINT_STR_FUNCTION, vec![], ControlTransfer::Branch {
+                cond: var("s"),
+                then_lab: start_label.clone(),
+                else_lab: null_label.clone(),
+            }),
+            runtime_block(&null_label, vec![], ControlTransfer::Fail { message: "NotAPointer".to_string() }),
+            runtime_block(&start_label, vec![
+                Primitive::GetElt { dest: "length".to_string(), arr: var("s"), idx: Value::Constant(0) },
+                Primitive::Assign { dest: "i".to_string(), value: Value::Constant(0) },
+            ], ControlTransfer::Jump { target: cond_label.clone() }),
+            runtime_block(&cond_label, vec![
+                binop("more", var("i"), "<", var("length")),
+            ], ControlTransfer::Branch {
+                cond: var("more"),
+                then_lab: body_label.clone(),
+                else_lab: done_label.clone(),
+            }),
+            runtime_block(&body_label, vec![
+                binop("slot", var("i"), "+", Value::Constant(1)),
+                Primitive::GetElt { dest: "char".to_string(), arr: var("s"), idx: var("slot") },
+                Primitive::Print { val: var("char") },
+                Primitive::Assign { dest: "i".to_string(), value: var("slot") },
+            ], ControlTransfer::Jump { target: cond_label }),
+            runtime_block(&done_label, vec![], ControlTransfer::Return { val: Value::Constant(0) }),
+        ];
+
+        self.functions.push(Function {
+            name: PRINT_STR_FUNCTION.to_string(),
+            args: vec!["this".to_string(), "s".to_string()],
+            blocks,
+        });
+    }
+
+    /*
+    1 if both strings have the same characters. the same object (or both null) is equal,
+    null against a real string is not
+
+        _strEquals(this, a, b):
+            %samePtr = %a == %b
+            if %samePtr then strEqTrue else strEqCheckA
+        strEqCheckA:
+            if %a then strEqCheckB else strEqFalse
+        strEqCheckB:
+            if %b then strEqLength else strEqFalse
+        strEqLength:
+            %length = getelt(%a, 0)
+            %otherLength = getelt(%b, 0)
+            %sameLength = %length == %otherLength
+            %i = 0
+            if %sameLength then strEqCond else strEqFalse
+        strEqCond:
+            %more = %i < %length
+            if %more then strEqBody else strEqTrue
+        strEqBody:
+            %slot = %i + 1
+            %charA = getelt(%a, %slot)
+            %charB = getelt(%b, %slot)
+            %sameChar = %charA == %charB
+            %i = %slot
+            if %sameChar then strEqCond else strEqFalse
+        strEqTrue:
+            ret 1
+        strEqFalse:
+            ret 0
+    */
+    fn gen_str_equals_function(&mut self) {
+        let check_a = self.gen_unique_label("strEqCheckA");
+        let check_b = self.gen_unique_label("strEqCheckB");
+        let length_label = self.gen_unique_label("strEqLength");
+        let cond_label = self.gen_unique_label("strEqCond");
+        let body_label = self.gen_unique_label("strEqBody");
+        let true_label = self.gen_unique_label("strEqTrue");
+        let false_label = self.gen_unique_label("strEqFalse");
+
+        let branch = |cond: &str, then_lab: &String, else_lab: &String| ControlTransfer::Branch {
+            cond: var(cond),
+            then_lab: then_lab.clone(),
+            else_lab: else_lab.clone(),
+        };
+
+        let blocks = vec![
+            runtime_block(STR_EQUALS_FUNCTION, vec![
+                binop("samePtr", var("a"), "==", var("b")),
+            ], branch("samePtr", &true_label, &check_a)),
+            runtime_block(&check_a, vec![], branch("a", &check_b, &false_label)),
+            runtime_block(&check_b, vec![], branch("b", &length_label, &false_label)),
+            runtime_block(&length_label, vec![
+                Primitive::GetElt { dest: "length".to_string(), arr: var("a"), idx: Value::Constant(0) },
+                Primitive::GetElt { dest: "otherLength".to_string(), arr: var("b"), idx: Value::Constant(0) },
+                binop("sameLength", var("length"), "==", var("otherLength")),
+                Primitive::Assign { dest: "i".to_string(), value: Value::Constant(0) },
+            ], branch("sameLength", &cond_label, &false_label)),
+            runtime_block(&cond_label, vec![
+                binop("more", var("i"), "<", var("length")),
+            ], branch("more", &body_label, &true_label)),
+            runtime_block(&body_label, vec![
+                binop("slot", var("i"), "+", Value::Constant(1)),
+                Primitive::GetElt { dest: "charA".to_string(), arr: var("a"), idx: var("slot") },
+                Primitive::GetElt { dest: "charB".to_string(), arr: var("b"), idx: var("slot") },
+                binop("sameChar", var("charA"), "==", var("charB")),
+                Primitive::Assign { dest: "i".to_string(), value: var("slot") },
+            ], branch("sameChar", &cond_label, &false_label)),
+            runtime_block(&true_label, vec![], ControlTransfer::Return { val: Value::Constant(1) }),
+            runtime_block(&false_label, vec![], ControlTransfer::Return { val: Value::Constant(0) }),
+        ];
+
+        self.functions.push(Function {
+            name: STR_EQUALS_FUNCTION.to_string(),
+            args: vec!["this".to_string(), "a".to_string(), "b".to_string()],
+            blocks,
+        });
+    }
+
+    fn gen_array_alloc_function(&mut self) {
+        let mut blocks = vec![];
+        let mut label = ARRAY_ALLOC_FUNCTION.to_string();
+
+        for size_class in 0..=MAX_ARRAY_SIZE_CLASS {
+            let capacity = 1i64 << size_class;
+            let alloc_label = self.gen_unique_label("arrayAlloc");
+            let next_label = if size_class == MAX_ARRAY_SIZE_CLASS {
+                self.gen_unique_label("arrayTooLarge")
+            } else {
+                self.gen_unique_label("arraySize")
+            };
+
+            let fits = self.gen_unique_variable("fits");
+            blocks.push(BasicBlock {
+                label,
+                primitives: vec![Primitive::BinOp {
+                    dest: fits.clone(),
+                    lhs: Value::Variable("n".to_string()),
+                    op: "<".to_string(),
+                    rhs: Value::Constant(capacity + 1),
+                }],
+                control_transfer: ControlTransfer::Branch {
+                    cond: Value::Variable(fits),
+                    then_lab: alloc_label.clone(),
+                    else_lab: next_label.clone(),
+                },
+            });
+
+            let array = self.gen_unique_variable("array");
+            blocks.push(BasicBlock {
+                label: alloc_label,
+                primitives: vec![
+                    Primitive::Alloc { dest: array.clone(), size: capacity + 1 },
+                    Primitive::SetElt {
+                        arr: Value::Variable(array.clone()),
+                        idx: Value::Constant(0),
+                        val: Value::Variable("n".to_string()),
+                    },
+                ],
+                control_transfer: ControlTransfer::Return { val: Value::Variable(array) },
+            });
+            label = next_label;
+        }
+
+        blocks.push(BasicBlock {
+            label,
+            primitives: vec![],
+            control_transfer: ControlTransfer::Fail { message: "NoSuchField".to_string() },
+        });
+
+        self.functions.push(Function {
+            name: ARRAY_ALLOC_FUNCTION.to_string(),
+            args: vec!["this".to_string(), "n".to_string()],
+            blocks,
+        });
+    }
+
+    // once a return, break, continue, or throw has ended the block (or an if, match, or try whose
+    // branches all do), the rest of the statements can never run (the type checker warns about them)
+    // so they are dropped instead of tacked on before the jump or into a merge block nothing reaches
+    fn gen_block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashMap::new());
+        for statement in statements {
+            self.gen_statement(statement);
+            if always_exits(statement) {
+                break;
+            }
+        }
+        self.scopes.pop();
+    }
+
+    // the ir variable a source name refers to here, header locals and args keep their own name
+    fn resolve(&self, name: &str) -> String {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
     fn gen_statement(&mut self, statement: &Statement) {
-        match statement {
+        match &statement.kind {
 
-            Statement::Assignment { variable, expression } => {
+            StatementKind::Assignment { variable, expression } => {
                 let val = self.gen_expression(expression);
 
                 self.push_instruction(Primitive::Assign {
-                    dest: variable.clone(), 
+                    dest: self.resolve(variable), 
+                    value: val,
+                });
+            }
+
+            // the initializer goes first so it still sees any outer variable with the same name
+            StatementKind::Declaration { name, typ, value } => {
+                let typ = typ.clone().unwrap_or_else(|| self.evaluate_type(value));
+                let val = self.gen_expression(value);
+
+                let ir_name = self.gen_local_variable(name);
+                self.type_environment.insert(ir_name.clone(), typ.clone());
+                self.var_types.insert(ir_name.clone(), typ);
+                self.scopes.last_mut()
+                    .expect("declarations are always inside a block")
+                    .insert(name.clone(), ir_name.clone());
+
+                self.push_instruction(Primitive::Assign {
+                    dest: ir_name,
                     value: val,
                 });
             }
 
-            Statement::Discard(expr) => {
+            StatementKind::Discard(expr) => {
                 self.gen_expression(expr);
             }
 
+            StatementKind::ArrayWrite { array, index, value } => {
+                let nullable = self.is_nullable(array);
+                let array = self.gen_expression(array);
+                let index = self.gen_expression(index);
+                let val = self.gen_expression(value);
+                let slot = self.gen_array_slot(&array, index, nullable);
+
+                self.push_instruction(Primitive::SetElt {
+                    arr: array,
+                    idx: slot,
+                    val,
+                });
+            }
+
             // no more tagging needed
-            Statement::Print(expression) => {
+            // a str prints one character code per line, print is the only output the ir has
+            StatementKind::Print(expression) => {
+                let is_string = strip_nullable(self.evaluate_type(expression)) == ast::Type::Str;
                 let val = self.gen_expression(expression);
-                self.push_instruction(Primitive::Print { val });
+                if is_string {
+                    self.uses_strings = true;
+                    let ignored = self.gen_unique_variable("printed");
+                    self.push_instruction(Primitive::Call {
+                        dest: ignored,
+                        func: Value::Code(PRINT_STR_FUNCTION.to_string()),
+                        receiver: Value::Constant(0),
+                        args: vec![val],
+                    });
+                } else {
+                    self.push_instruction(Primitive::Print { val });
+                }
             }
 
-            Statement::Return(expression) => {
+            StatementKind::Return(expression) => {
                 let val = self.gen_expression(expression);
 
                 self.current_block.control_transfer = ControlTransfer::Return { val };
-                self.current_block_has_explicit_return = true;
+                self.current_block_has_explicit_transfer = true;
             }
 
-            Statement::FieldWrite { base, field, value } => {
-                let expression = Expression::FieldWrite { 
+            StatementKind::FieldWrite { base, field, value } => {
+                let expression = Expression::new(ExpressionKind::FieldWrite { 
                     base: Box::new(base.clone()), 
                     field_name: field.clone(), 
                     value: Box::new(value.clone()),
-                };
+                }, statement.span);
                 self.gen_expression(&expression);
             }
 
@@ -658,7 +1673,7 @@ impl IRBuilder {
                 statement2
             }
             */
-            Statement::If { condition, then_body, else_body } => {
+            StatementKind::If { condition, then_body, else_body } => {
                 // here we'll want to make an if label with a condition
                 // %condition = expression
 
@@ -706,28 +1721,24 @@ impl IRBuilder {
                     },
                     then_label,
                 );
-                for statement in then_body {
-                    self.gen_statement(statement);
-                }
+                self.gen_block(then_body);
 
                 // here we need to check if the then body is returning something
                 // because if the then body returns something, we need to handle the return and not
                 // just jump blindly
                 // we can check this just by checking the current basic block's control transfer, if it is a return
                 let then_control_transfer = 
-                    if self.current_block_has_explicit_return {
+                    if self.current_block_has_explicit_transfer {
                         self.current_block.control_transfer.clone()
                     } else {
                         ControlTransfer::Jump { target: merge_label.clone() }
                     };
                 self.finish_block(then_control_transfer, else_label);
 
-                for statement in else_body {
-                    self.gen_statement(statement);
-                }
+                self.gen_block(else_body);
 
                 let else_control_transfer = 
-                    if self.current_block_has_explicit_return {
+                    if self.current_block_has_explicit_transfer {
                         self.current_block.control_transfer.clone()
                     } else {
                         ControlTransfer::Jump { target: merge_label.clone() }
@@ -735,7 +1746,7 @@ impl IRBuilder {
                 self.finish_block(else_control_transfer, merge_label);
             }
 
-            Statement::IfOnly { condition, body } => {
+            StatementKind::IfOnly { condition, body } => {
 
                 let then_label = self.gen_unique_label("then");
                 let merge_label = self.gen_unique_label("merge");
@@ -762,12 +1773,10 @@ impl IRBuilder {
                     then_label.clone(),
                 );
 
-                for statement in body {
-                    self.gen_statement(statement);
-                }
+                self.gen_block(body);
 
                 let then_control_transfer = 
-                    if self.current_block_has_explicit_return {
+                    if self.current_block_has_explicit_transfer {
                         self.current_block.control_transfer.clone()
                     } else {
                         ControlTransfer::Jump { target: merge_label.clone() }
@@ -775,7 +1784,7 @@ impl IRBuilder {
                 self.finish_block(then_control_transfer, merge_label);
             }
 
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 let cond_label = self.gen_unique_label("condLabel");
                 let body_label = self.gen_unique_label("whileBody");
                 let merge_label = self.gen_unique_label("whileMerge");
@@ -810,12 +1819,12 @@ impl IRBuilder {
                     body_label
                 );
 
-                for statement in body {
-                    self.gen_statement(statement);
-                }
+                self.loop_labels.push((merge_label.clone(), cond_label.clone()));
+                self.gen_block(body);
+                self.loop_labels.pop();
 
                 let while_control_transfer = 
-                    if self.current_block_has_explicit_return {
+                    if self.current_block_has_explicit_transfer {
                         self.current_block.control_transfer.clone()
                     } else {
                         ControlTransfer::Jump { target: cond_label }
@@ -826,10 +1835,432 @@ impl IRBuilder {
                     merge_label,
                 );
             }
+
+            /*
+            for i = a to b: { body }
+
+                    i = a
+                    %forEnd = b
+                    %done = i > %forEnd         # signed, see gen_signed_compare
+                    if %done then forMerge else forBody
+                forBody:
+                    body
+                    jump forStep
+                forStep:                        # continue lands here so i still goes up
+                    %last = i == %forEnd        # checked before adding 1 so a b at the top of the range cant wrap around
+                    if %last then forMerge else forNext
+                forNext:
+                    i = i + 1
+                    jump forBody
+                forMerge:
+            */
+            StatementKind::For { variable, from, to, body } => {
+                let variable = &self.resolve(variable);
+                let body_label = self.gen_unique_label("forBody");
+                let step_label = self.gen_unique_label("forStep");
+                let next_label = self.gen_unique_label("forNext");
+                let merge_label = self.gen_unique_label("forMerge");
+
+                let start = self.gen_expression(from);
+                self.push_instruction(Primitive::Assign {
+                    dest: variable.clone(),
+                    value: start,
+                });
+                let end = self.gen_expression(to);
+                let end_var = self.gen_unique_variable("forEnd");
+                self.var_types.insert(end_var.clone(), ast::Type::Int);
+                self.push_instruction(Primitive::Assign {
+                    dest: end_var.clone(),
+                    value: end,
+                });
+
+                let done = self.gen_signed_compare(Value::Variable(variable.clone()), ">", Value::Variable(end_var.clone()));
+                self.finish_block(
+                    ControlTransfer::Branch {
+                        cond: done,
+                        then_lab: merge_label.clone(),
+                        else_lab: body_label.clone(),
+                    },
+                    body_label.clone(),
+                );
+
+                self.loop_labels.push((merge_label.clone(), step_label.clone()));
+                self.gen_block(body);
+                self.loop_labels.pop();
+
+                let body_control_transfer =
+                    if self.current_block_has_explicit_transfer {
+                        self.current_block.control_transfer.clone()
+                    } else {
+                        ControlTransfer::Jump { target: step_label.clone() }
+                    };
+                self.finish_block(body_control_transfer, step_label);
+
+                let last = self.gen_binop("last", Value::Variable(variable.clone()), "==", Value::Variable(end_var));
+                self.finish_block(
+                    ControlTransfer::Branch {
+                        cond: last,
+                        then_lab: merge_label.clone(),
+                        else_lab: next_label.clone(),
+                    },
+                    next_label,
+                );
+
+                let next = self.gen_binop("next", Value::Variable(variable.clone()), "+", Value::Constant(1));
+                self.push_instruction(Primitive::Assign {
+                    dest: variable.clone(),
+                    value: next,
+                });
+                self.finish_block(ControlTransfer::Jump { target: body_label }, merge_label);
+            }
+
+            /*
+            match s: { Circle(r): { ... } Rect(w, h): { ... } _: { ... } }
+
+                    %tag = load(%s)
+                    # binary search on the tag down to the arm for it
+                    %below = %tag < 1
+                    if %below then arm0 else tagTest
+                arm0:
+                    %r = getelt(%s, 1)
+                    body
+                    jump matchMerge
+                ...
+                matchMerge:
+
+            an arm that can never run (its variant was already handled) gets no block at all
+            */
+            StatementKind::Match { value, arms } => {
+                let enum_name = self.class_of(value);
+                let declared = self.enums.iter()
+                    .find(|declared| declared.name == enum_name)
+                    .cloned()
+                    .unwrap_or_else(|| panic!("Enum {} not found", enum_name));
+                let value = self.gen_expression(value);
+
+                let tag = self.gen_unique_variable("tag");
+                self.var_types.insert(tag.clone(), ast::Type::Int);
+                self.push_instruction(Primitive::Load {
+                    dest: tag.clone(),
+                    addr: value.clone(),
+                });
+
+                // the first arm that covers each tag, the type checker made sure there is one
+                let arm_for_tag: Vec<usize> = declared.variants.iter()
+                    .map(|variant| arms.iter()
+                        .position(|arm| arm.variant.as_ref().is_none_or(|name| *name == variant.name))
+                        .expect("type checker rejects a match that misses a variant"))
+                    .collect();
+                let labels: Vec<Option<String>> = (0..arms.len())
+                    .map(|i| arm_for_tag.contains(&i).then(|| self.gen_unique_label("arm")))
+                    .collect();
+                let merge_label = self.gen_unique_label("matchMerge");
+
+                let targets: Vec<String> = arm_for_tag.iter().map(|arm| labels[*arm].clone().unwrap()).collect();
+                let mut tests = vec![];
+                let (primitives, transfer) = self.gen_match_tree(&Value::Variable(tag), &targets, 0, &mut tests);
+                self.current_block.primitives.extend(primitives);
+
+                let reachable: Vec<(usize, String)> = labels.iter().enumerate()
+                    .filter_map(|(i, label)| label.clone().map(|label| (i, label)))
+                    .collect();
+                self.finish_block(transfer, reachable[0].1.clone());
+                // the tests go after the block they branch out of, blocks[0] has to stay the entry
+                self.current_function_blocks.extend(tests);
+
+                for (position, (i, _)) in reachable.iter().enumerate() {
+                    let arm = &arms[*i];
+                    let fields = arm.variant.as_ref()
+                        .and_then(|name| declared.variant(name))
+                        .map(|(_, variant)| variant.fields.as_slice())
+                        .unwrap_or_default();
+
+                    // the bindings are just reads out of the value, one fresh variable each
+                    self.scopes.push(HashMap::new());
+                    for (slot, (binding, field)) in arm.bindings.iter().zip(fields).enumerate() {
+                        if binding == "_" {
+                            continue;
+                        }
+                        let ir_name = self.gen_local_variable(binding);
+                        self.type_environment.insert(ir_name.clone(), field.typ.clone());
+                        self.var_types.insert(ir_name.clone(), field.typ.clone());
+                        self.scopes.last_mut().unwrap().insert(binding.clone(), ir_name.clone());
+                        self.push_instruction(Primitive::GetElt {
+                            dest: ir_name,
+                            arr: value.clone(),
+                            idx: Value::Constant(1 + slot as i64),
+                        });
+                    }
+                    self.gen_block(&arm.body);
+                    self.scopes.pop();
+
+                    let arm_control_transfer =
+                        if self.current_block_has_explicit_transfer {
+                            self.current_block.control_transfer.clone()
+                        } else {
+                            ControlTransfer::Jump { target: merge_label.clone() }
+                        };
+                    let next_label = reachable.get(position + 1)
+                        .map(|(_, label)| label.clone())
+                        .unwrap_or_else(|| merge_label.clone());
+                    self.finish_block(arm_control_transfer, next_label);
+                }
+            }
+
+            // the thrown object goes in the exception state, then it's a jump like break
+            StatementKind::Throw(value) => {
+                let val = self.gen_expression(value);
+                self.push_instruction(Primitive::Store {
+                    addr: var(EXCEPTION_STATE),
+                    val,
+                });
+
+                self.current_block.control_transfer = ControlTransfer::Jump { target: self.current_handler() };
+                self.current_block_has_explicit_transfer = true;
+            }
+
+            /*
+            try { body } catch (e: A) { ... } catch (e: B) { ... }
+
+                    body                    # throws and failed call checks in here go to catch
+                    jump tryMerge
+                catch:
+                    %thrown = load(%exceptionState)
+                    %thrownVtable = load(%thrown)
+                    %isA = %thrownVtable == @vtblA    # one test for A and each of its subclasses
+                    if %isA then catchBody else catchTest
+                catchBody:
+                    store(%exceptionState, 0)
+                    e = %thrown
+                    ...
+                    jump tryMerge
+                catchTest:
+                    ...B the same way, if it isnt one of those either it goes on to the next handler out
+                tryMerge:
+            */
+            StatementKind::Try { body, catches } => {
+                let dispatch_label = self.gen_unique_label("catch");
+                let merge_label = self.gen_unique_label("tryMerge");
+
+                self.handlers.push(dispatch_label.clone());
+                self.gen_block(body);
+                self.handlers.pop();
+
+                let body_control_transfer =
+                    if self.current_block_has_explicit_transfer {
+                        self.current_block.control_transfer.clone()
+                    } else {
+                        ControlTransfer::Jump { target: merge_label.clone() }
+                    };
+                self.finish_block(body_control_transfer, dispatch_label);
+
+                let thrown = self.gen_unique_variable("thrown");
+                self.push_instruction(Primitive::Load {
+                    dest: thrown.clone(),
+                    addr: var(EXCEPTION_STATE),
+                });
+                let vtable = self.gen_unique_variable("thrownVtable");
+                self.push_instruction(Primitive::Load {
+                    dest: vtable.clone(),
+                    addr: Value::Variable(thrown.clone()),
+                });
+
+                for (i, catch) in catches.iter().enumerate() {
+                    let caught = self.gen_catch_test(&vtable, catch);
+                    let body_label = self.gen_unique_label("catchBody");
+                    let next_label = if i + 1 < catches.len() {
+                        self.gen_unique_label("catchTest")
+                    } else {
+                        // none of them took it, keep the exception pending for whoever is further out
+                        self.current_handler()
+                    };
+                    self.finish_block(
+                        ControlTransfer::Branch {
+                            cond: caught,
+                            then_lab: body_label.clone(),
+                            else_lab: next_label.clone(),
+                        },
+                        body_label,
+                    );
+
+                    self.push_instruction(Primitive::Store {
+                        addr: var(EXCEPTION_STATE),
+                        val: Value::Constant(0),
+                    });
+                    self.scopes.push(HashMap::new());
+                    let ir_name = self.gen_local_variable(&catch.name);
+                    self.type_environment.insert(ir_name.clone(), catch.typ.clone());
+                    self.var_types.insert(ir_name.clone(), catch.typ.clone());
+                    self.scopes.last_mut().unwrap().insert(catch.name.clone(), ir_name.clone());
+                    self.push_instruction(Primitive::Assign {
+                        dest: ir_name,
+                        value: Value::Variable(thrown.clone()),
+                    });
+                    self.gen_block(&catch.body);
+                    self.scopes.pop();
+
+                    let catch_control_transfer =
+                        if self.current_block_has_explicit_transfer {
+                            self.current_block.control_transfer.clone()
+                        } else {
+                            ControlTransfer::Jump { target: merge_label.clone() }
+                        };
+                    // the last catch's else already went to the outer handler, so what follows is the merge
+                    let after = if i + 1 < catches.len() { next_label } else { merge_label.clone() };
+                    self.finish_block(catch_control_transfer, after);
+                }
+            }
+
+            // same trick as return, the block just ends in a jump instead of falling through
+            StatementKind::Break | StatementKind::Continue => {
+                let (break_label, continue_label) = self.loop_labels.last()
+                    .expect("type checker rejects break/continue outside a loop");
+                let target = if matches!(statement.kind, StatementKind::Break) { break_label } else { continue_label };
+
+                self.current_block.control_transfer = ControlTransfer::Jump { target: target.clone() };
+                self.current_block_has_explicit_transfer = true;
+            }
+        }
+    }
+
+    // picks targets[tag - first] by splitting the range of tags in half until every tag left goes to the same arm.
+    // returns what ends the block doing the first test, every test below that becomes its own block in tests
+    fn gen_match_tree(&mut self, tag: &Value, targets: &[String], first: usize,
+                      tests: &mut Vec<BasicBlock>) -> (Vec<Primitive>, ControlTransfer) {
+        if targets.iter().all(|target| *target == targets[0]) {
+            return (vec![], ControlTransfer::Jump { target: targets[0].clone() });
         }
+
+        let mid = targets.len() / 2;
+        let below = self.gen_unique_variable("below");
+        self.var_types.insert(below.clone(), ast::Type::Bool);
+        let test = binop(&below, tag.clone(), "<", Value::Constant((first + mid) as i64));
+
+        let then_lab = self.gen_match_subtree(tag, &targets[..mid], first, tests);
+        let else_lab = self.gen_match_subtree(tag, &targets[mid..], first + mid, tests);
+        (vec![test], ControlTransfer::Branch { cond: var(&below), then_lab, else_lab })
     }
 
-    fn gen_method(&mut self, class: &ast::Class, method: &ast::Method) {
+    // the label to go to for these tags, a new test block unless they all go to one arm
+    fn gen_match_subtree(&mut self, tag: &Value, targets: &[String], first: usize, tests: &mut Vec<BasicBlock>) -> String {
+        if targets.iter().all(|target| *target == targets[0]) {
+            return targets[0].clone();
+        }
+        let label = self.gen_unique_label("tagTest");
+        let (primitives, transfer) = self.gen_match_tree(tag, targets, first, tests);
+        tests.push(BasicBlock { label: label.clone(), primitives, control_transfer: transfer });
+        label
+    }
+
+    // whether the thrown object's vtable is one of the classes the catch takes, as a bool temp
+    fn gen_catch_test(&mut self, vtable: &str, catch: &Catch) -> Value {
+        let ast::Type::ClassType(caught) = &catch.typ else {
+            panic!("catch of non-class type {}", catch.typ);
+        };
+        // the caught class itself, every subclass, and every class that implements it if it's an interface
+        let classes: Vec<String> = self.classes.iter()
+            .filter(|class| self.ancestors(&class.name).iter()
+                .any(|ancestor| ancestor.name == *caught || ancestor.interfaces.iter().any(|(name, _)| name == caught)))
+            .map(|class| class.name.clone())
+            .collect();
+
+        let result = self.gen_unique_variable("caught");
+        self.var_types.insert(result.clone(), ast::Type::Bool);
+        self.push_instruction(Primitive::Assign {
+            dest: result.clone(),
+            value: Value::Constant(0),
+        });
+        for class in classes {
+            let is_class = self.gen_binop("isClass", var(vtable), "==", Value::Global(format!("vtbl{}", class)));
+            self.push_instruction(Primitive::BinOp {
+                dest: result.clone(),
+                lhs: Value::Variable(result.clone()),
+                op: "|".to_string(),
+                rhs: is_class,
+            });
+        }
+        Value::Variable(result)
+    }
+
+    // the body of a lambda, with its captures copied out of the closure into fresh variables first
+    fn gen_lambda(&mut self, pending: PendingLambda) {
+        let PendingLambda { name, lambda, captures } = pending;
+        self.type_environment.clear();
+        let mut args = vec!["this".to_string()];
+        if self.uses_exceptions {
+            args.push(EXCEPTION_STATE.to_string());
+        }
+        for arg in &lambda.args {
+            self.type_environment.insert(arg.name.clone(), arg.typ.clone());
+            args.push(arg.name.clone());
+        }
+
+        self.current_block = BasicBlock {
+            label: name.clone(),
+            primitives: vec![],
+            control_transfer: ControlTransfer::Return { val: Value::Constant(0) },
+        };
+        self.current_function_blocks = vec![];
+        self.current_block_has_explicit_transfer = false;
+
+        self.scopes.push(HashMap::new());
+        for (i, (captured, typ)) in captures.into_iter().enumerate() {
+            let ir_name = self.gen_local_variable(&captured);
+            self.type_environment.insert(ir_name.clone(), typ.clone());
+            self.var_types.insert(ir_name.clone(), typ);
+            self.scopes.last_mut().unwrap().insert(captured, ir_name.clone());
+            self.push_instruction(Primitive::GetElt {
+                dest: ir_name,
+                arr: var("this"),
+                idx: Value::Constant(1 + i as i64),
+            });
+        }
+        self.gen_block(&lambda.body);
+        self.scopes.pop();
+
+        self.finish_function(name, args);
+    }
+
+    /*
+    what a method reference with n arguments runs, the closure holds the object and the method:
+
+        _boundMethod1(this, a0):
+            %boundObject = getelt(%this, 1)
+            %boundMethod = getelt(%this, 2)
+            %boundResult = call(%boundMethod, %boundObject, %a0)
+            ret %boundResult
+
+    an exception from the method is still pending when this returns, so the caller's check sees it
+    */
+    fn gen_bound_method_function(&mut self, arity: usize) {
+        let mut args = vec!["this".to_string()];
+        let mut call_args = vec![];
+        if self.uses_exceptions {
+            args.push(EXCEPTION_STATE.to_string());
+            call_args.push(var(EXCEPTION_STATE));
+        }
+        for i in 0..arity {
+            let arg = format!("a{}", i);
+            call_args.push(var(&arg));
+            args.push(arg);
+        }
+
+        let name = format!("{}{}", BOUND_METHOD_PREFIX, arity);
+        let block = runtime_block(&name, vec![
+            Primitive::GetElt { dest: "boundObject".to_string(), arr: var("this"), idx: Value::Constant(1) },
+            Primitive::GetElt { dest: "boundMethod".to_string(), arr: var("this"), idx: Value::Constant(2) },
+            Primitive::Call {
+                dest: "boundResult".to_string(),
+                func: var("boundMethod"),
+                receiver: var("boundObject"),
+                args: call_args,
+            },
+        ], ControlTransfer::Return { val: var("boundResult") });
+        self.functions.push(Function { name, args, blocks: vec![block] });
+    }
+
+    // this_type is None for top level functions, they still take a this slot like every ir function
+    fn gen_method(&mut self, function_name: String, this_type: Option<ast::Type>, method: &ast::Method) {
         /*
         This is code from the IR parser.
         It shows thats when parsing a basic block for a mthod, it looks for arguments as
@@ -850,19 +2281,22 @@ impl IRBuilder {
         }
         */
         self.type_environment.clear();
-        self.type_environment.insert("this".to_string(), ast::Type::ClassType(class.name.clone()));
-        for (arg, typ) in &method.args {
-            self.type_environment.insert(arg.clone(), typ.clone());
+        if let Some(this_type) = this_type {
+            self.type_environment.insert("this".to_string(), this_type);
         }
-        for (local, typ) in &method.locals {
-            self.type_environment.insert(local.clone(), typ.clone());
+        for arg in &method.args {
+            self.type_environment.insert(arg.name.clone(), arg.typ.clone());
+        }
+        for local in &method.locals {
+            self.type_environment.insert(local.name.clone(), local.typ.clone());
         }
-
-        let function_name = format!("{}{}", method.name, class.name);
 
         let mut args = vec!["this".to_string()];
-        for (arg, _) in &method.args {
-            args.push(arg.clone());
+        if self.uses_exceptions {
+            args.push(EXCEPTION_STATE.to_string());
+        }
+        for arg in &method.args {
+            args.push(arg.name.clone());
         }
 
         // just build the basic blocks and push the function at the end of the statement evaluation
@@ -872,36 +2306,47 @@ impl IRBuilder {
             control_transfer: ControlTransfer::Return { val: Value::Constant(0) },
         };
         self.current_function_blocks = vec![];
-        self.current_block_has_explicit_return = false;
+        self.current_block_has_explicit_transfer = false;
 
         // initialize the locals to tagged 0s
-        for (local, _) in &method.locals {
+        for local in &method.locals {
             self.push_instruction(Primitive::Assign {
-                dest: local.clone(),
+                dest: local.name.clone(),
                 value: Value::Constant(0),
             });
         }
 
-        for statement in &method.body {
-            self.gen_statement(statement);
-        }
+        self.gen_block(&method.body);
 
         self.finish_function(function_name, args);
     }
 
     pub fn gen_program(&mut self, program: &ast::Program) -> ir::Program {
         self.classes = program.classes.clone();
+        self.interfaces = program.interfaces.clone();
+        self.enums = program.enums.clone();
+        self.top_level_functions = program.functions.clone();
+        self.uses_exceptions = program.classes.iter()
+            .flat_map(|class| class.methods.iter().chain(&class.constructor))
+            .chain(&program.functions)
+            .any(|method| throws(&method.body))
+            || throws(&program.main_body);
         self.gen_class_metadata(program);
 
         for class in &program.classes {
-            for method in &class.methods {
-                self.gen_method(class, method);
+            for method in class.methods.iter().chain(&class.constructor) {
+                let function_name = format!("{}{}", method.name, class.name);
+                self.gen_method(function_name, Some(ast::Type::ClassType(class.name.clone())), method);
             }
         }
 
+        for function in &program.functions {
+            self.gen_method(format!("{}{}", FUNCTION_PREFIX, function.name), None, function);
+        }
+
         self.type_environment.clear();
-        for (local, typ) in &program.main_locals {
-            self.type_environment.insert(local.clone(), typ.clone());
+        for local in &program.main_locals {
+            self.type_environment.insert(local.name.clone(), local.typ.clone());
         }
 
         // generating main block
@@ -911,26 +2356,103 @@ impl IRBuilder {
             control_transfer: ControlTransfer::Return { val: Value::Constant(0) },
         };
         self.current_function_blocks = vec![];
-        self.current_block_has_explicit_return = false;
+        self.current_block_has_explicit_transfer = false;
 
         // must initialize main locals, just make them tagged 0
-        for (local, _) in &program.main_locals {
+        for local in &program.main_locals {
             self.push_instruction(Primitive::Assign {
-                dest: local.clone(),
+                dest: local.name.clone(),
                 value: Value::Constant(0),
             });
         }
 
-        for statement in &program.main_body {
-            self.gen_statement(statement);
+        if self.uses_exceptions {
+            self.push_instruction(Primitive::Alloc { dest: EXCEPTION_STATE.to_string(), size: 1 });
+            self.push_instruction(Primitive::Store { addr: var(EXCEPTION_STATE), val: Value::Constant(0) });
         }
 
+        self.gen_block(&program.main_body);
+
         self.finish_function("main".to_string(), vec![]);
 
+        // a lambda inside a lambda adds another one to the list while this runs
+        while let Some(pending) = self.pending_lambdas.pop() {
+            self.gen_lambda(pending);
+        }
+        for arity in std::mem::take(&mut self.bound_method_arities) {
+            self.gen_bound_method_function(arity);
+        }
+
+        if self.uses_arrays {
+            self.gen_array_alloc_function();
+        }
+        if self.uses_strings {
+            self.gen_print_str_function();
+            self.gen_str_equals_function();
+        }
+
         ir::Program {
             globals: self.globals.clone(),
             functions: self.functions.clone(),
             var_types: self.var_types.clone(),
         }
     }
-}
\ No newline at end of file
+}
+
+// str? and int[]? are laid out just like str and int[], 0 being null
+fn strip_nullable(typ: ast::Type) -> ast::Type {
+    match typ {
+        ast::Type::Nullable(inner) => *inner,
+        other => other,
+    }
+}
+
+// whether any throw appears in these statements, nested blocks and lambda bodies included
+fn throws(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match &statement.kind {
+        StatementKind::Throw(_) => true,
+        StatementKind::Assignment { expression, .. }
+        | StatementKind::Discard(expression)
+        | StatementKind::Return(expression)
+        | StatementKind::Print(expression)
+        | StatementKind::Declaration { value: expression, .. } => lambda_throws(expression),
+        StatementKind::FieldWrite { base, value, .. } => lambda_throws(base) || lambda_throws(value),
+        StatementKind::ArrayWrite { array, index, value } =>
+            lambda_throws(array) || lambda_throws(index) || lambda_throws(value),
+        StatementKind::If { condition, then_body, else_body } =>
+            lambda_throws(condition) || throws(then_body) || throws(else_body),
+        StatementKind::IfOnly { condition, body } | StatementKind::While { condition, body } =>
+            lambda_throws(condition) || throws(body),
+        StatementKind::For { from, to, body, .. } => lambda_throws(from) || lambda_throws(to) || throws(body),
+        StatementKind::Match { value, arms } => lambda_throws(value) || arms.iter().any(|arm| throws(&arm.body)),
+        StatementKind::Try { body, catches } => throws(body) || catches.iter().any(|catch| throws(&catch.body)),
+        StatementKind::Break | StatementKind::Continue => false,
+    })
+}
+
+// whether a lambda somewhere in this expression throws
+fn lambda_throws(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Lambda(lambda) => throws(&lambda.body),
+        _ => expression.operands().into_iter().any(lambda_throws),
+    }
+}
+
+// the type of a method or function used as a value
+fn function_type(signature: &ast::MethodSignature) -> ast::Type {
+    let args = signature.args.iter().map(|arg| arg.typ.clone()).collect();
+    ast::Type::Function(args, Box::new(signature.return_type.clone()))
+}
+
+// small constructors for the hand written runtime helpers
+fn runtime_block(label: &str, primitives: Vec<Primitive>, control_transfer: ControlTransfer) -> BasicBlock {
+    BasicBlock { label: label.to_string(), primitives, control_transfer }
+}
+
+fn var(name: &str) -> Value {
+    Value::Variable(name.to_string())
+}
+
+fn binop(dest: &str, lhs: Value, op: &str, rhs: Value) -> Primitive {
+    Primitive::BinOp { dest: dest.to_string(), lhs, op: op.to_string(), rhs }
+}
@@ -8,12 +8,16 @@ mod ir;
 mod ir_builder;
 mod cfg;
 mod typechecker;
+mod span;
+mod diagnostics;
+mod monomorphize;
+mod imports;
+mod captures;
 
-use tokenizer::Tokenizer;
-use parser::Parser;
 use ir_builder::IRBuilder;
 use cfg::CFG;
 
+use crate::diagnostics::{Diagnostic, ErrorFormat};
 use crate::typechecker::TypeChecker;
 
 fn main() {
@@ -22,7 +26,10 @@ fn main() {
     let mut use_ssa = true;
     let mut use_vn = true;
     let mut use_fold = true;
+    let mut error_format = ErrorFormat::Human;
     let mut filename: Option<&String> = None;
+    // extra directories to look for imports in, after the importing file's own directory
+    let mut search_paths: Vec<std::path::PathBuf> = vec![];
 
     let mut i = 1;
     while i < args.len() {
@@ -33,9 +40,21 @@ fn main() {
             "--no-vn"   => use_vn = false,
             "--fold"    => use_fold = true,
             "--no-fold" => use_fold = false,
+            "--error-format=human" => error_format = ErrorFormat::Human,
+            "--error-format=json"  => error_format = ErrorFormat::Json,
+            // -I dir or -Idir
+            "-I" => {
+                i += 1;
+                let Some(dir) = args.get(i) else {
+                    eprintln!("Error: -I needs a directory");
+                    std::process::exit(1);
+                };
+                search_paths.push(dir.into());
+            }
+            arg if arg.starts_with("-I") => search_paths.push(arg[2..].into()),
             arg if arg.starts_with("--") => {
                 eprintln!("Unknown flag: {}", arg);
-                eprintln!("Usage: ./comp [--ssa|--no-ssa] [--vn|--no-vn] [--fold|--no-fold] <source_file>");
+                eprintln!("Usage: ./comp [--ssa|--no-ssa] [--vn|--no-vn] [--fold|--no-fold] [--error-format=human|json] [-I <dir>]... <source_file>");
                 std::process::exit(1);
             }
             _ => {
@@ -50,7 +69,7 @@ fn main() {
     }
 
     let filename = filename.unwrap_or_else(|| {
-        eprintln!("Usage: ./comp [--ssa|--no-ssa] [--vn|--no-vn] [--fold|--no-fold] <source_file>");
+        eprintln!("Usage: ./comp [--ssa|--no-ssa] [--vn|--no-vn] [--fold|--no-fold] [--error-format=human|json] [-I <dir>]... <source_file>");
         std::process::exit(1);
     });
 
@@ -60,12 +79,31 @@ fn main() {
             std::process::exit(1);
         });
 
-    let tokenizer = Tokenizer::new(source);
-    let mut parser = Parser::new(tokenizer);
-    let ast = parser.parse_program();
+    // pulls in every import and glues the files into one program
+    let (files, ast) = imports::load(filename, source, &search_paths);
+    let ast = match ast {
+        Ok(ast) => ast,
+        Err(diagnostics) => {
+            diagnostics::emit(&diagnostics, &files, error_format);
+            std::process::exit(1);
+        }
+    };
+
+    let mut checker = TypeChecker::new(&ast);
+    let result = checker.check_program(&ast);
+    let mut diagnostics: Vec<Diagnostic> = checker.take_warnings().iter().map(Diagnostic::from).collect();
+    if let Err(errors) = result {
+        diagnostics.extend(errors.iter().map(Diagnostic::from));
+        diagnostics::emit(&diagnostics, &files, error_format);
+        std::process::exit(1);
+    }
+    // warnings alone dont stop the compile
+    if !diagnostics.is_empty() {
+        diagnostics::emit(&diagnostics, &files, error_format);
+    }
 
-    let checker = TypeChecker::new(&ast);
-    checker.check_program(&ast);
+    // one plain class per generic instantiation, the ir builder doesnt know about generics
+    let ast = monomorphize::monomorphize(&ast);
 
     let mut ir_builder = IRBuilder::new();
     let mut ir_program = ir_builder.gen_program(&ast);
@@ -82,6 +120,7 @@ fn main() {
         }
 
         if use_fold {
+            cfg.simplify_bools(&mut ir_program.functions[i], &ir_program.var_types);
             cfg.fold_constants(&mut ir_program.functions[i]);
         }
     }
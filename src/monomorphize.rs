@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Class, Declaration, Enum, Method, Program, Type};
+use crate::expression::{Expression, ExpressionKind, Lambda};
+use crate::statement::{Catch, MatchArm, Statement, StatementKind};
+
+/*
+Generic classes only exist up to the type checker. The ir builder never sees one:
+every instantiation like Stack<int> gets its own plain copy of the class with T replaced by int
+(its own vtable, its own methods), and every use of Stack<int> is pointed at that copy.
+
+    Stack<int>            -> Stack_Of_int_End
+    Pair<str, Stack<A>>   -> Pair_Of_str_And_Stack_Of_A_End_End
+
+The names end up in ir labels and globals, which take letters, digits, and _. Identifiers in the
+source can't have a _ in them, so no class the program declares can have the same name as a copy.
+A generic class nobody instantiates produces no code at all
+*/
+
+pub fn monomorphize(program: &Program) -> Program {
+    let mut mono = Monomorphizer {
+        generics: program.classes.iter()
+            .filter(|class| !class.type_params.is_empty())
+            .map(|class| (class.name.clone(), class.clone()))
+            .collect(),
+        seen: HashSet::new(),
+        queue: vec![],
+    };
+    let none = HashMap::new();
+
+    let mut classes: Vec<Class> = program.classes.iter()
+        .filter(|class| class.type_params.is_empty())
+        .map(|class| mono.class(class.clone(), &none))
+        .collect();
+    let enums = program.enums.iter()
+        .map(|declared| mono.enum_fields(declared.clone()))
+        .collect();
+    let functions = program.functions.iter()
+        .map(|function| mono.method(function.clone(), &none))
+        .collect();
+    let main_locals = mono.declarations(program.main_locals.clone(), &none);
+    let main_body = mono.block(program.main_body.clone(), &none);
+
+    // specializing a class can turn up more instantiations, Stack<int> might use a Node<int> inside
+    while let Some((name, args)) = mono.queue.pop() {
+        let generic = mono.generics[&name].clone();
+        let type_args: HashMap<String, Type> = generic.type_params.iter().cloned().zip(args.iter().cloned()).collect();
+        let mut class = mono.class(generic, &type_args);
+        class.name = mangle(&name, &args);
+        class.type_params.clear();
+        classes.push(class);
+    }
+
+    Program {
+        imports: program.imports.clone(),
+        classes,
+        interfaces: program.interfaces.clone(),
+        enums,
+        functions,
+        main_locals,
+        main_body,
+    }
+}
+
+struct Monomorphizer {
+    generics: HashMap<String, Class>,
+    // mangled names of every instantiation already queued
+    seen: HashSet<String>,
+    // generic class name and the concrete type arguments still waiting for their copy
+    queue: Vec<(String, Vec<Type>)>,
+}
+
+impl Monomorphizer {
+    // fills in the type parameters, then swaps every instantiation for its specialized class
+    fn resolve(&mut self, typ: &Type, type_args: &HashMap<String, Type>) -> Type {
+        self.concrete(&typ.substitute(type_args))
+    }
+
+    fn concrete(&mut self, typ: &Type) -> Type {
+        match typ {
+            Type::Generic(name, args) => {
+                let mangled = mangle(name, args);
+                if self.seen.insert(mangled.clone()) {
+                    self.queue.push((name.clone(), args.clone()));
+                }
+                Type::ClassType(mangled)
+            }
+            Type::Array(element) => Type::Array(Box::new(self.concrete(element))),
+            Type::Nullable(inner) => Type::Nullable(Box::new(self.concrete(inner))),
+            Type::Function(args, ret) =>
+                Type::Function(args.iter().map(|arg| self.concrete(arg)).collect(), Box::new(self.concrete(ret))),
+            other => other.clone(),
+        }
+    }
+
+    fn class(&mut self, mut class: Class, type_args: &HashMap<String, Type>) -> Class {
+        class.fields = self.declarations(class.fields, type_args);
+        class.constructor = class.constructor.map(|constructor| self.method(constructor, type_args));
+        class.methods = class.methods.into_iter().map(|method| self.method(method, type_args)).collect();
+        class
+    }
+
+    // enums arent generic, but a variant's fields can still use something like Stack<int>
+    fn enum_fields(&mut self, mut declared: Enum) -> Enum {
+        let none = HashMap::new();
+        for variant in &mut declared.variants {
+            variant.fields = self.declarations(std::mem::take(&mut variant.fields), &none);
+        }
+        declared
+    }
+
+    fn method(&mut self, mut method: Method, type_args: &HashMap<String, Type>) -> Method {
+        method.args = self.declarations(method.args, type_args);
+        method.locals = self.declarations(method.locals, type_args);
+        method.return_type = self.resolve(&method.return_type, type_args);
+        method.body = self.block(method.body, type_args);
+        method
+    }
+
+    fn declarations(&mut self, declarations: Vec<Declaration>, type_args: &HashMap<String, Type>) -> Vec<Declaration> {
+        declarations.into_iter()
+            .map(|declaration| Declaration { typ: self.resolve(&declaration.typ, type_args), ..declaration })
+            .collect()
+    }
+
+    fn block(&mut self, statements: Vec<Statement>, type_args: &HashMap<String, Type>) -> Vec<Statement> {
+        statements.into_iter().map(|statement| self.statement(statement, type_args)).collect()
+    }
+
+    fn statement(&mut self, mut statement: Statement, type_args: &HashMap<String, Type>) -> Statement {
+        statement.kind = match statement.kind {
+            StatementKind::Assignment { variable, expression } =>
+                StatementKind::Assignment { variable, expression: self.expression(expression, type_args) },
+            StatementKind::Declaration { name, typ, value } => StatementKind::Declaration {
+                name,
+                typ: typ.map(|typ| self.resolve(&typ, type_args)),
+                value: self.expression(value, type_args),
+            },
+            StatementKind::Discard(expression) => StatementKind::Discard(self.expression(expression, type_args)),
+            StatementKind::FieldWrite { base, field, value } => StatementKind::FieldWrite {
+                base: self.expression(base, type_args),
+                field,
+                value: self.expression(value, type_args),
+            },
+            StatementKind::ArrayWrite { array, index, value } => StatementKind::ArrayWrite {
+                array: self.expression(array, type_args),
+                index: self.expression(index, type_args),
+                value: self.expression(value, type_args),
+            },
+            StatementKind::If { condition, then_body, else_body } => StatementKind::If {
+                condition: self.expression(condition, type_args),
+                then_body: self.block(then_body, type_args),
+                else_body: self.block(else_body, type_args),
+            },
+            StatementKind::IfOnly { condition, body } => StatementKind::IfOnly {
+                condition: self.expression(condition, type_args),
+                body: self.block(body, type_args),
+            },
+            StatementKind::While { condition, body } => StatementKind::While {
+                condition: self.expression(condition, type_args),
+                body: self.block(body, type_args),
+            },
+            StatementKind::For { variable, from, to, body } => StatementKind::For {
+                variable,
+                from: self.expression(from, type_args),
+                to: self.expression(to, type_args),
+                body: self.block(body, type_args),
+            },
+            StatementKind::Match { value, arms } => StatementKind::Match {
+                value: self.expression(value, type_args),
+                arms: arms.into_iter()
+                    .map(|arm| MatchArm { body: self.block(arm.body, type_args), ..arm })
+                    .collect(),
+            },
+            StatementKind::Throw(expression) => StatementKind::Throw(self.expression(expression, type_args)),
+            StatementKind::Try { body, catches } => StatementKind::Try {
+                body: self.block(body, type_args),
+                catches: catches.into_iter()
+                    .map(|catch| Catch {
+                        typ: self.resolve(&catch.typ, type_args),
+                        body: self.block(catch.body, type_args),
+                        ..catch
+                    })
+                    .collect(),
+            },
+            StatementKind::Return(expression) => StatementKind::Return(self.expression(expression, type_args)),
+            StatementKind::Print(expression) => StatementKind::Print(self.expression(expression, type_args)),
+            kind @ (StatementKind::Break | StatementKind::Continue) => kind,
+        };
+        statement
+    }
+
+    fn expressions(&mut self, expressions: Vec<Expression>, type_args: &HashMap<String, Type>) -> Vec<Expression> {
+        expressions.into_iter().map(|expression| self.expression(expression, type_args)).collect()
+    }
+
+    fn expression(&mut self, mut expression: Expression, type_args: &HashMap<String, Type>) -> Expression {
+        expression.kind = match expression.kind {
+            ExpressionKind::Binop { lhs, op, rhs } => ExpressionKind::Binop {
+                lhs: Box::new(self.expression(*lhs, type_args)),
+                op,
+                rhs: Box::new(self.expression(*rhs, type_args)),
+            },
+            ExpressionKind::Unary { op, operand } =>
+                ExpressionKind::Unary { op, operand: Box::new(self.expression(*operand, type_args)) },
+            ExpressionKind::MethodCall { base, method_name, args } => ExpressionKind::MethodCall {
+                base: Box::new(self.expression(*base, type_args)),
+                method_name,
+                args: self.expressions(args, type_args),
+            },
+            ExpressionKind::FunctionCall { function_name, args } =>
+                ExpressionKind::FunctionCall { function_name, args: self.expressions(args, type_args) },
+            ExpressionKind::FieldRead { base, field_name } =>
+                ExpressionKind::FieldRead { base: Box::new(self.expression(*base, type_args)), field_name },
+            ExpressionKind::FieldWrite { base, field_name, value } => ExpressionKind::FieldWrite {
+                base: Box::new(self.expression(*base, type_args)),
+                field_name,
+                value: Box::new(self.expression(*value, type_args)),
+            },
+            // @Stack<T> inside Stack<T> is whatever this copy of Stack is specialized to
+            ExpressionKind::ClassRef { class_name, type_args: class_args, args } => {
+                let class_name = if class_args.is_empty() {
+                    class_name
+                } else {
+                    match self.resolve(&Type::Generic(class_name, class_args), type_args) {
+                        Type::ClassType(mangled) => mangled,
+                        _ => unreachable!(),
+                    }
+                };
+                ExpressionKind::ClassRef { class_name, type_args: vec![], args: self.expressions(args, type_args) }
+            }
+            ExpressionKind::Variant { enum_name, variant, args } =>
+                ExpressionKind::Variant { enum_name, variant, args: self.expressions(args, type_args) },
+            ExpressionKind::Null(typ) => ExpressionKind::Null(self.resolve(&typ, type_args)),
+            ExpressionKind::ArrayAlloc { element, length } => ExpressionKind::ArrayAlloc {
+                element: self.resolve(&element, type_args),
+                length: Box::new(self.expression(*length, type_args)),
+            },
+            ExpressionKind::Index { array, index } => ExpressionKind::Index {
+                array: Box::new(self.expression(*array, type_args)),
+                index: Box::new(self.expression(*index, type_args)),
+            },
+            ExpressionKind::Length(array) => ExpressionKind::Length(Box::new(self.expression(*array, type_args))),
+            ExpressionKind::Lambda(lambda) => ExpressionKind::Lambda(Lambda {
+                args: self.declarations(lambda.args, type_args),
+                return_type: self.resolve(&lambda.return_type, type_args),
+                body: self.block(lambda.body, type_args),
+            }),
+            ExpressionKind::MethodRef { base, method_name } =>
+                ExpressionKind::MethodRef { base: Box::new(self.expression(*base, type_args)), method_name },
+            kind @ (ExpressionKind::ThisExpr | ExpressionKind::Constant(_) | ExpressionKind::Bool(_)
+                | ExpressionKind::StringLiteral(_) | ExpressionKind::Variable(_)) => kind,
+        };
+        expression
+    }
+}
+
+fn mangle(name: &str, args: &[Type]) -> String {
+    let args: Vec<String> = args.iter().map(mangle_type).collect();
+    format!("{}_Of_{}_End", name, args.join("_And_"))
+}
+
+fn mangle_type(typ: &Type) -> String {
+    match typ {
+        Type::Int => "int".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Str => "str".to_string(),
+        Type::ClassType(name) => name.clone(),
+        Type::Generic(name, args) => mangle(name, args),
+        Type::Array(element) => format!("{}_Array", mangle_type(element)),
+        Type::Nullable(inner) => format!("{}_Nullable", mangle_type(inner)),
+        Type::Function(args, ret) => {
+            let args: Vec<String> = args.iter().map(mangle_type).collect();
+            format!("Fn_Of_{}_To_{}_End", args.join("_And_"), mangle_type(ret))
+        }
+    }
+}
@@ -1,63 +1,240 @@
-use crate::token::{Token, TokenType};
+use crate::token::{Operator, Token, TokenType, UnaryOperator};
 use crate::tokenizer::Tokenizer;
-use crate::expression::Expression;
-use crate::statement::Statement;
-use crate::ast::{Class, Method, Program, Type};
+use crate::expression::{Expression, ExpressionKind, Lambda};
+use crate::statement::{Catch, MatchArm, Statement, StatementKind};
+use crate::ast::{Class, Declaration, Enum, Import, Interface, Method, MethodSignature, Program, Type, Variant};
+use crate::span::Span;
 
 pub struct Parser {
     tok: Tokenizer,
+
+    // errors we already recovered from
+    // parse_program hands all of them back at the end instead of stopping at the first
+    errors: Vec<ParseError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    // what we wanted, ex: expected `:` after if condition
+    pub message: String,
+    pub span: Span,
+    // empty when there is no single token that would have fit, like "an expression"
+    pub expected: Vec<TokenType>,
+    pub found: Token,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.found {
+            Token::Error(message) => write!(f, "{}", message),
+            found => write!(f, "{}, found {}", self.message, found),
+        }
+    }
 }
 
+type ParseResult<T> = Result<T, ParseError>;
+
+/*
+Error recovery:
+
+Every parse_* function bails out with Err on the first token it doesnt like, without consuming it.
+The loops that parse lists of statements, methods and classes catch that error, save it,
+and skip ahead to a point where parsing can pick back up (synchronize_*).
+Then we keep going, so one run reports every syntax error in the file instead of just the first one
+*/
 impl Parser {
     pub fn new(tok: Tokenizer) -> Self {
-        Parser { tok }
+        Parser { tok, errors: vec![] }
     }
 
-    pub fn parse_expr(&mut self) -> Expression {
-        match self.tok.next() {
-            Token::Eof => panic!("No expression to parse: EOF"),
+    // span from start up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.tok.previous_span())
+    }
 
-            Token::Number(n) => Expression::Constant(n),
+    // builds an error for the token peek() would return, leaving it in place
+    // what is a description of what we wanted instead
+    fn unexpected(&mut self, expected: Vec<TokenType>, what: &str) -> ParseError {
+        let found = self.tok.peek().clone();
+        let span = self.tok.peek_span();
+        ParseError { message: format!("expected {}", what), span, expected, found }
+    }
 
-            Token::Identifier(name) => Expression::Variable(name),
+    // consumes the next token if it has the expected type, otherwise errors without consuming it
+    // context finishes the sentence, like "after if condition"
+    fn expect(&mut self, expected: TokenType, context: &str) -> ParseResult<()> {
+        if self.tok.peek().get_type() == expected {
+            self.tok.next();
+            Ok(())
+        } else {
+            let what = if context.is_empty() {
+                expected.to_string()
+            } else {
+                format!("{} {}", expected, context)
+            };
+            Err(self.unexpected(vec![expected], &what))
+        }
+    }
 
-            Token::LeftParen => {
-                let lhs = self.parse_expr();
+    fn expect_identifier(&mut self, what: &str) -> ParseResult<String> {
+        match self.tok.peek() {
+            Token::Identifier(_) => match self.tok.next() {
+                Token::Identifier(name) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected(vec![TokenType::Identifier], what)),
+        }
+    }
 
-                let op = match self.tok.next() {
-                    Token::Operator(c) => c,
-                    other => panic!("Expected operator but found {:?}", other),
-                };
+    // steps over a token while recovering from an error. a bad token from the tokenizer
+    // (an unsupported character, an unterminated comment) still gets reported on the way past,
+    // unless it is the one the error we are recovering from already points at
+    fn skip_token(&mut self) -> Token {
+        if let Token::Error(_) = self.tok.peek() {
+            let span = self.tok.peek_span();
+            if self.errors.last().is_none_or(|last| last.span != span) {
+                let error = self.unexpected(vec![], "a token");
+                self.errors.push(error);
+            }
+        }
+        self.tok.next()
+    }
 
-                let rhs = self.parse_expr();
+    fn eat_comma(&mut self) {
+        if self.tok.peek().get_type() == TokenType::Comma {
+            self.tok.next();
+        }
+    }
 
-                match self.tok.next() {
-                    Token::RightParen => {},
-                    other => panic!("Expected right parenthesis but found {:?}", other),
-                }
+    pub fn parse_expr(&mut self) -> ParseResult<Expression> {
+        self.parse_binary(0)
+    }
+
+    // the operator peek() would return if it can sit between two expressions
+    // & and ^ are field read and method call at the start of an expression,
+    // but once we already have a left hand side they can only mean bitwise and/xor
+    fn peek_infix_operator(&mut self) -> Option<Operator> {
+        match self.tok.peek() {
+            Token::Operator(op) => Some(op.clone()),
+            Token::Ampersand => Some(Operator::BitwiseAnd),
+            Token::Caret => Some(Operator::BitwiseXor),
+            _ => None,
+        }
+    }
+
+    /*
+    precedence climbing:
+    parse one operand, then keep folding in operators that bind at least as tightly as min_precedence.
+    the right hand side only takes operators that bind strictly tighter than the current one,
+    which is what makes a - b - c group as (a - b) - c
+
+    a + b * c == d  ->  ((a + (b * c)) == d)
+    */
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<Expression> {
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(op) = self.peek_infix_operator() {
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.tok.next();
+
+            let rhs = self.parse_binary(precedence + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expression::new(ExpressionKind::Binop {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            }, span);
+        }
+
+        Ok(lhs)
+    }
+
+    // -e, !e and ~e bind tighter than any infix operator, so -a * b is (-a) * b
+    fn parse_unary(&mut self) -> ParseResult<Expression> {
+        let start = self.tok.peek_span();
+        let op = match self.tok.peek() {
+            Token::Operator(Operator::Minus) => UnaryOperator::Negate,
+            Token::Not => UnaryOperator::Not,
+            Token::Tilde => UnaryOperator::Complement,
+            _ => return self.parse_primary(),
+        };
+        self.tok.next();
+
+        // a minus right in front of a number is just a negative literal
+        if op == UnaryOperator::Negate
+            && let Token::Number(n) = self.tok.peek() {
+            let value = n.wrapping_neg();
+            self.tok.next();
+            return Ok(Expression::new(ExpressionKind::Constant(value), self.span_from(start)));
+        }
 
-                Expression::Binop {
-                    lhs: Box::new(lhs),
-                    op,
-                    rhs: Box::new(rhs),
+        let operand = self.parse_unary()?;
+        Ok(Expression::new(ExpressionKind::Unary {
+            op,
+            operand: Box::new(operand),
+        }, self.span_from(start)))
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<Expression> {
+        self.parse_atom(false)
+    }
+
+    // the base of &base.field or ^base.method(), where the . after @A belongs to the field
+    // read or method call, so @A.m isn't taken for a variant of an enum A
+    fn parse_member_base(&mut self) -> ParseResult<Expression> {
+        self.parse_atom(true)
+    }
+
+    fn parse_atom(&mut self, member_base: bool) -> ParseResult<Expression> {
+        let start = self.tok.peek_span();
+        let kind = match self.tok.peek().clone() {
+            Token::Number(n) => {
+                self.tok.next();
+                ExpressionKind::Constant(n)
+            }
+
+            Token::StringLiteral(text) => {
+                self.tok.next();
+                ExpressionKind::StringLiteral(text)
+            }
+
+            // a variable, or a call to a top level function, f(a, b)
+            Token::Identifier(name) => {
+                self.tok.next();
+                if self.tok.peek().get_type() == TokenType::LeftParen {
+                    self.tok.next();
+                    let args = self.parse_call_args()?;
+                    ExpressionKind::FunctionCall { function_name: name, args }
+                } else {
+                    ExpressionKind::Variable(name)
                 }
             }
 
+            // grouping, this also covers the old fully parenthesized (lhs op rhs) form
+            Token::LeftParen => {
+                self.tok.next();
+                let inner = self.parse_expr()?;
+
+                self.expect(TokenType::RightParen, "to close parenthesized expression")?;
+
+                // widen the span to cover the parens
+                inner.kind
+            }
+
             Token::Ampersand => {
                 // reads &base.fieldname
-                let base = self.parse_expr();
+                // the base is a primary so &this.val + 1 reads the field before adding
+                self.tok.next();
+                let base = self.parse_member_base()?;
 
-                match self.tok.next() {
-                    Token::Dot => {},
-                    other => panic!("Expected . but found {:?}", other),
-                }
+                self.expect(TokenType::Dot, "in field read")?;
 
-                let field_name = match self.tok.next() {
-                    Token::Identifier(name) => name,
-                    other => panic!("Expected field name but found {:?}", other),
-                };
+                let field_name = self.expect_identifier("field name")?;
 
-                Expression::FieldRead {
+                ExpressionKind::FieldRead {
                     base: Box::new(base),
                     field_name,
                 }
@@ -66,441 +243,993 @@ impl Parser {
             Token::Caret => {
                 // this is method call
                 //^base.method(args1, 2, 3..)
-                let base = self.parse_expr();
+                // without the (args) it's a reference to the method bound to base, ^base.method
+                self.tok.next();
+                let base = self.parse_member_base()?;
 
-                match self.tok.next() {
-                    Token::Dot => {},
-                    other => panic!("Expected . but found {:?}", other),
+                self.expect(TokenType::Dot, "in method call")?;
+
+                let method_name = self.expect_identifier("method name")?;
+
+                if self.tok.peek().get_type() == TokenType::LeftParen {
+                    self.tok.next();
+
+                    let args = self.parse_call_args()?;
+
+                    ExpressionKind::MethodCall {
+                        base: Box::new(base),
+                        method_name,
+                        args,
+                    }
+                } else {
+                    ExpressionKind::MethodRef { base: Box::new(base), method_name }
                 }
+            }
+
+            // function(x:int) returning int: { ... }, a lambda
+            // returning is optional like on a method, the body is a block since it sits inside an expression
+            Token::Function => {
+                self.tok.next();
+                self.expect(TokenType::LeftParen, "after `function` in a lambda")?;
 
-                let method_name = match self.tok.next() {
-                    Token::Identifier(name) => name,
-                    other => panic!("Expected valid method name but found {:?}", other),
+                let args = self.parse_declarations(TokenType::RightParen, "argument name")?;
+
+                let return_type = if self.tok.peek().get_type() == TokenType::Returning {
+                    self.tok.next();
+                    self.parse_type()?
+                } else {
+                    Type::Int
                 };
 
-                match self.tok.next() {
-                    Token::LeftParen => {},
-                    other => panic!("Expected left paren but found {:?}", other),
+                self.expect(TokenType::Colon, "after lambda arguments")?;
+
+                let body = self.parse_block("lambda")?;
+
+                ExpressionKind::Lambda(Lambda { args, return_type, body })
+            }
+
+            Token::AtSign => {
+                // this is class refernce
+                // @ClassName, or @ClassName(args) to pass arguments to its constructor
+                // or an array allocation, @int[n], @A[n], @int[][n] for an array of int arrays
+                // a generic class takes its type arguments right after the name, @Stack<int>(args), @Stack<int>[n]
+                // and @Shape.Circle(args) makes a value of an enum
+                // objects in a new array start out null, so an array of them is allocated as @A?[n],
+                // and the same goes for strs and arrays, @str?[n] and @int[]?[n]
+                self.tok.next();
+                let class_name = self.expect_identifier("class name after @")?;
+                let type_args = if self.at_type_args() { self.parse_type_args()? } else { vec![] };
+
+                let nullable = self.tok.peek().get_type() == TokenType::Question;
+                if nullable {
+                    self.tok.next();
                 }
 
-                // now parsing arguments to method
-                let mut args = Vec::<Expression>::new();
-                while self.tok.peek().get_type() != TokenType::RightParen {
-                    let arg = self.parse_expr();
-                    args.push(arg);
-                    
-                    if self.tok.peek().get_type() == TokenType::Comma {
+                // @Shape.Circle(5), a value of an enum
+                if !member_base && type_args.is_empty() && !nullable && self.tok.peek().get_type() == TokenType::Dot {
+                    self.tok.next();
+                    let variant = self.expect_identifier("variant name after `.`")?;
+                    let args = if self.tok.peek().get_type() == TokenType::LeftParen {
+                        self.tok.next();
+                        self.parse_call_args()?
+                    } else {
+                        vec![]
+                    };
+                    ExpressionKind::Variant { enum_name: class_name, variant, args }
+                } else if nullable || self.tok.peek().get_type() == TokenType::LeftBracket {
+                    let mut element = if type_args.is_empty() {
+                        named_type(class_name)
+                    } else {
+                        Type::Generic(class_name, type_args)
+                    };
+                    if nullable {
+                        element = Type::Nullable(Box::new(element));
+                    }
+                    loop {
+                        self.expect(TokenType::LeftBracket, "in array allocation")?;
+                        if self.tok.peek().get_type() != TokenType::RightBracket {
+                            break;
+                        }
                         self.tok.next();
+                        element = Type::Array(Box::new(element));
+                        if self.tok.peek().get_type() == TokenType::Question {
+                            self.tok.next();
+                            element = Type::Nullable(Box::new(element));
+                        }
                     }
+                    let length = self.parse_expr()?;
+                    self.expect(TokenType::RightBracket, "after array length")?;
+                    ExpressionKind::ArrayAlloc { element, length: Box::new(length) }
+                } else {
+                    let args = if self.tok.peek().get_type() == TokenType::LeftParen {
+                        self.tok.next();
+                        self.parse_call_args()?
+                    } else {
+                        vec![]
+                    };
+                    ExpressionKind::ClassRef { class_name, type_args, args }
                 }
+            }
 
+            // len(e)
+            Token::Len => {
                 self.tok.next();
-
-                Expression::MethodCall {
-                    base: Box::new(base),
-                    method_name,
-                    args,
-                }
+                self.expect(TokenType::LeftParen, "after len")?;
+                let array = self.parse_expr()?;
+                self.expect(TokenType::RightParen, "after len argument")?;
+                ExpressionKind::Length(Box::new(array))
             }
 
-            Token::AtSign => {
-                // this is class refernce
-                // @ClassName
-                let class_name = match self.tok.next() {
-                    Token::Identifier(name) => name,
-                    other => panic!("Expected valid class name but found {:?}", other),
-                };
+            Token::This => {
+                self.tok.next();
+                ExpressionKind::ThisExpr
+            }
 
-                Expression::ClassRef(class_name)
+            Token::True => {
+                self.tok.next();
+                ExpressionKind::Bool(true)
             }
 
-            Token::This => Expression::ThisExpr,
+            Token::False => {
+                self.tok.next();
+                ExpressionKind::Bool(false)
+            }
 
             Token::Null => {
-                match self.tok.next() {
-                    Token::Colon => {},
-                    other => panic!("Expected a ':' after null, but got {:?}", other),
+                self.tok.next();
+                self.expect(TokenType::Colon, "after null")?;
+                // null:A?, null:str?, or null:int[]?
+                ExpressionKind::Null(self.parse_type()?)
+            }
+
+            _ => return Err(self.unexpected(vec![], "an expression")),
+        };
+
+        let primary = Expression::new(kind, self.span_from(start));
+        self.parse_indexing(primary)
+    }
+
+    // arguments up to (and including) the closing paren, the open paren is already eaten
+    fn parse_call_args(&mut self) -> ParseResult<Vec<Expression>> {
+        let mut args = Vec::<Expression>::new();
+        while self.tok.peek().get_type() != TokenType::RightParen {
+            let arg = self.parse_expr()?;
+            args.push(arg);
+            self.eat_comma();
+        }
+
+        self.tok.next();
+        Ok(args)
+    }
+
+    // any number of [i] after an expression, a[i][j] is (a[i])[j]
+    fn parse_indexing(&mut self, mut array: Expression) -> ParseResult<Expression> {
+        while self.tok.peek().get_type() == TokenType::LeftBracket {
+            self.tok.next();
+            let index = self.parse_expr()?;
+            self.expect(TokenType::RightBracket, "after array index")?;
+            let span = self.span_from(array.span);
+            array = Expression::new(ExpressionKind::Index {
+                array: Box::new(array),
+                index: Box::new(index),
+            }, span);
+        }
+        Ok(array)
+    }
+
+    // the target of an array store was parsed as a normal a[i] read, pull it back apart
+    fn array_write(&mut self, target: Expression) -> ParseResult<StatementKind> {
+        let ExpressionKind::Index { array, index } = target.kind else {
+            return Err(self.unexpected(vec![TokenType::Dot], "in field write"));
+        };
+        self.expect(TokenType::Equals, "in array write")?;
+        let value = self.parse_expr()?;
+        Ok(StatementKind::ArrayWrite { array: *array, index: *index, value })
+    }
+
+    // { <newline> <one or more statements> }
+    // context is the construct that owns the block, for error messages
+    fn parse_block(&mut self, context: &str) -> ParseResult<Vec<Statement>> {
+        self.expect(TokenType::LeftBrace, &format!("after {}", context))?;
+        let body = self.parse_statements();
+        self.expect(TokenType::RightBrace, &format!("to close {} block", context))?;
+        Ok(body)
+    }
+
+    // parses statements until something that closes the enclosing construct
+    // errors inside a statement are recorded and we skip to the next one
+    fn parse_statements(&mut self) -> Vec<Statement> {
+        let mut body = Vec::<Statement>::new();
+        while !self.at_boundary() {
+            let before = self.tok.peek_span().start;
+            match self.parse_statement() {
+                Ok(statement) => body.push(statement),
+                Err(error) => {
+                    let line = error.span.line;
+                    self.errors.push(error);
+                    self.synchronize_statement(line);
+
+                    // make sure we always move forward, otherwise we'd report the same token forever
+                    if self.tok.peek_span().start == before && !self.at_boundary() {
+                        self.skip_token();
+                    }
                 }
-                let class_name = match self.tok.next() {
-                    Token::Identifier(n) => n,
-                    other => panic!("Expected class name after null:, got {:?}", other),
-                };
-                Expression::Null(class_name)
             }
+        }
+        body
+    }
 
-            other => panic!("Token {:?} is not a valid start of an expression", other),
+    // Circle(r): { ... }, Empty: { ... }, or _: { ... }
+    fn parse_match_arm(&mut self) -> ParseResult<MatchArm> {
+        let start = self.tok.peek_span();
+        let name = self.expect_identifier("variant name or `_`")?;
+        let variant = if name == "_" { None } else { Some(name) };
+
+        let mut bindings = Vec::<String>::new();
+        if variant.is_some() && self.tok.peek().get_type() == TokenType::LeftParen {
+            self.tok.next();
+            while self.tok.peek().get_type() != TokenType::RightParen {
+                bindings.push(self.expect_identifier("name for the variant's field")?);
+                self.eat_comma();
+            }
+            self.tok.next();
         }
+        let span = self.span_from(start);
+
+        self.expect(TokenType::Colon, "after match pattern")?;
+        let body = self.parse_block("match arm")?;
+
+        Ok(MatchArm { variant, bindings, body, span })
     }
 
-    pub fn parse_statement(&mut self) -> Statement {
-        match self.tok.peek() {
-            
+    // catch (e: A) { ... }
+    fn parse_catch(&mut self) -> ParseResult<Catch> {
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Catch, "after try block")?;
+        self.expect(TokenType::LeftParen, "after `catch`")?;
+        let Declaration { name, typ, .. } = self.parse_declaration("name of the caught object")?;
+        self.expect(TokenType::RightParen, "after caught type")?;
+        let span = self.span_from(start);
+
+        let body = self.parse_block("catch")?;
+
+        Ok(Catch { name, typ, body, span })
+    }
+
+    // tokens that end a run of statements
+    fn at_boundary(&mut self) -> bool {
+        matches!(self.tok.peek().get_type(),
+            TokenType::RightBrace | TokenType::RightBracket | TokenType::Method | TokenType::Constructor
+            | TokenType::Class | TokenType::Interface | TokenType::Enum | TokenType::Function | TokenType::Main | TokenType::Eof)
+    }
+
+    fn at_statement_start(&mut self) -> bool {
+        matches!(self.tok.peek().get_type(),
+            TokenType::Return | TokenType::Print | TokenType::If | TokenType::IfOnly
+            | TokenType::While | TokenType::For | TokenType::Match | TokenType::Throw | TokenType::Try | TokenType::Break | TokenType::Continue | TokenType::Var
+            | TokenType::Not | TokenType::Identifier)
+    }
+
+    // statements arent newline terminated, but in practice they are one per line
+    // so skip to the first thing that can start a statement on a later line than the error,
+    // stepping over any nested blocks so we dont stop inside one
+    fn synchronize_statement(&mut self, error_line: usize) {
+        let mut depth = 0usize;
+        loop {
+            if depth == 0 && self.at_boundary() {
+                return;
+            }
+            if depth == 0 && self.at_statement_start() && self.tok.peek_span().line > error_line {
+                return;
+            }
+            match self.skip_token() {
+                Token::LeftBrace | Token::LeftBracket => depth += 1,
+                Token::RightBrace | Token::RightBracket => depth = depth.saturating_sub(1),
+                Token::Eof => return,
+                _ => {}
+            }
+        }
+    }
+
+    // skip to the next method or the end of the class
+    fn synchronize_member(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.tok.peek().get_type() {
+                TokenType::Eof | TokenType::Class | TokenType::Interface | TokenType::Enum | TokenType::Function | TokenType::Main => return,
+                TokenType::Method | TokenType::Constructor | TokenType::RightBracket if depth == 0 => return,
+                TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.skip_token();
+        }
+    }
+
+    // skip to the next class, interface, enum, function, import, or main
+    fn synchronize_class(&mut self) {
+        while !matches!(self.tok.peek().get_type(),
+            TokenType::Class | TokenType::Interface | TokenType::Enum | TokenType::Function | TokenType::Import
+            | TokenType::Main | TokenType::Eof) {
+            self.skip_token();
+        }
+    }
+
+    pub fn parse_statement(&mut self) -> ParseResult<Statement> {
+        let start = self.tok.peek_span();
+        let kind = match self.tok.peek() {
+
             // return e
             Token::Return => {
                 self.tok.next();
-                let expression = self.parse_expr();
-                Statement::Return(expression)
+                let expression = self.parse_expr()?;
+                StatementKind::Return(expression)
             }
-            
+
             // print(e)
             Token::Print => {
                 self.tok.next();
 
-                match self.tok.next() {
-                    Token::LeftParen => {},
-                    other => panic!("Expected ( fter print, got {:?}", other),
-                }
+                self.expect(TokenType::LeftParen, "after print")?;
 
-                let expr = self.parse_expr();
+                let expr = self.parse_expr()?;
 
-                match self.tok.next() {
-                    Token::RightParen => {},
-                    other => panic!("Expected ) after print expression, got {:?}", other),
-                }
+                self.expect(TokenType::RightParen, "after print expression")?;
 
-                Statement::Print(expr)
+                StatementKind::Print(expr)
             }
 
             // if e: { <newline> <one or more statements> } else { <newline> <one or more statements> }
             Token::If => {
                 self.tok.next();
-                let condition = self.parse_expr();
+                let condition = self.parse_expr()?;
 
-                match self.tok.next() {
-                    Token::Colon => {},
-                    other => panic!("Expected : after if condiiton, got {:?}", other),
-                }
+                self.expect(TokenType::Colon, "after if condition")?;
 
-                match self.tok.next() {
-                    Token::LeftBrace => {},
-                    other => panic!("Expected {{ after if:, got {:?}", other),
-                }
+                let then_body = self.parse_block("if")?;
 
-                let mut then_body = Vec::<Statement>::new();
-                while self.tok.peek().get_type() != TokenType::RightBrace {
-                    then_body.push(self.parse_statement());
-                }
+                let else_body = self.parse_else(true)?;
+
+                StatementKind::If { condition, then_body, else_body }
+            }
+
+            // ifonly e: { <newline> <one or more statements> }
+            Token::IfOnly => {
                 self.tok.next();
+                let condition = self.parse_expr()?;
 
-                match self.tok.next() {
-                    Token::Else => {},
-                    other => panic!("Expected else after if block, got {:?}", other),
-                }
+                self.expect(TokenType::Colon, "after ifonly condition")?;
 
-                match self.tok.next() {
-                    Token::LeftBrace => {},
-                    other => panic!("Expected {{ after else, got {:?}", other),
-                }
+                let body = self.parse_block("ifonly")?;
+
+                StatementKind::IfOnly { condition, body }
+            }
+
+            // while e: { <newline> <one or more statements> }
+            Token::While => {
+                self.tok.next();
+                let condition = self.parse_expr()?;
+
+                self.expect(TokenType::Colon, "after while condition")?;
+
+                let body = self.parse_block("while")?;
+
+                StatementKind::While { condition, body }
+            }
+
+            // match e: { Variant(x, y): { ... } Other: { ... } _: { ... } }
+            Token::Match => {
+                self.tok.next();
+                let value = self.parse_expr()?;
 
-                let mut else_body = Vec::<Statement>::new();
+                self.expect(TokenType::Colon, "after match value")?;
+                self.expect(TokenType::LeftBrace, "after match")?;
+
+                let mut arms = Vec::<MatchArm>::new();
                 while self.tok.peek().get_type() != TokenType::RightBrace {
-                    else_body.push(self.parse_statement());
+                    arms.push(self.parse_match_arm()?);
                 }
                 self.tok.next();
-                
-                Statement::If { condition, then_body, else_body }
+
+                StatementKind::Match { value, arms }
             }
 
-            // ifonly e: { <newline> <one or more statements> }
-            Token::IfOnly => {
+            // throw e
+            Token::Throw => {
                 self.tok.next();
-                let condition = self.parse_expr();
+                let value = self.parse_expr()?;
+                StatementKind::Throw(value)
+            }
 
-                match self.tok.next() {
-                    Token::Colon => {},
-                    other => panic!("Expected : after if condition, got {:?}", other),
-                }
+            // try { ... } catch (e: A) { ... }, with at least one catch
+            Token::Try => {
+                self.tok.next();
+                let body = self.parse_block("try")?;
 
-                match self.tok.next() {
-                    Token::LeftBrace => {},
-                    other => panic!("Expected {{ after if:, got {:?}", other),
+                let mut catches = Vec::<Catch>::new();
+                while self.tok.peek().get_type() == TokenType::Catch || catches.is_empty() {
+                    catches.push(self.parse_catch()?);
                 }
 
-                let mut body = Vec::<Statement>::new();
-                while self.tok.peek().get_type() != TokenType::RightBrace {
-                    body.push(self.parse_statement());
-                }
+                StatementKind::Try { body, catches }
+            }
+
+            // for i = a to b: { <newline> <one or more statements> }
+            Token::For => {
                 self.tok.next();
-                
-                Statement::IfOnly { condition, body }
+                let variable = self.expect_identifier("loop variable after `for`")?;
+
+                self.expect(TokenType::Equals, "after loop variable")?;
+
+                let from = self.parse_expr()?;
+
+                self.expect(TokenType::To, "after start of for range")?;
+
+                let to = self.parse_expr()?;
+
+                self.expect(TokenType::Colon, "after for range")?;
+
+                let body = self.parse_block("for")?;
+
+                StatementKind::For { variable, from, to, body }
             }
 
-            // while e: { <newline> <one or more statements> }
-            Token::While => {
+            // var x = e, the type comes from e
+            Token::Var => {
                 self.tok.next();
-                let condition = self.parse_expr();
-                
-                match self.tok.next() {
-                    Token::Colon => {},
-                    other => panic!("Expected : after while condition , got {:?}", other),
-                }
-                
-                match self.tok.next() {
-                    Token::LeftBrace => {},
-                    other => panic!("Expected {{ after while:, got {:?}", other),
-                }
-                
-                let mut body = Vec::<Statement>::new();
-                while self.tok.peek().get_type() != TokenType::RightBrace {
-                    body.push(self.parse_statement());
-                }
+                let name = self.expect_identifier("variable name after `var`")?;
+
+                self.expect(TokenType::Equals, "after variable name")?;
+
+                let value = self.parse_expr()?;
+
+                StatementKind::Declaration { name, typ: None, value }
+            }
+
+            Token::Break => {
+                self.tok.next();
+                StatementKind::Break
+            }
+
+            Token::Continue => {
                 self.tok.next();
-                
-                Statement::While { condition, body }
+                StatementKind::Continue
             }
 
-            // !e.f = e for field update
+            // !e.f = e for field update, or !e[i] = e to store into an array
             Token::Not => {
                 self.tok.next();
 
-                let base = self.parse_expr();
-                
-                match self.tok.next() {
-                    Token::Dot => {},
-                    other => panic!("Expected . in field write, got {:?}", other),
+                let base = self.parse_expr()?;
+
+                if self.tok.peek().get_type() != TokenType::Dot {
+                    let kind = self.array_write(base)?;
+                    return Ok(Statement::new(kind, self.span_from(start)));
                 }
+                self.expect(TokenType::Dot, "in field write")?;
 
-                let field = match self.tok.next() {
-                    Token::Identifier(name) => name,
-                    other => panic!("EXpected field name, got {:?}", other),
-                };
+                let field = self.expect_identifier("field name")?;
 
-                match self.tok.next() {
-                    Token::Equals => {},
-                    other => panic!("Expected = in field write, got {:?}", other),
-                }
+                self.expect(TokenType::Equals, "in field write")?;
+
+                let value = self.parse_expr()?;
 
-                let value = self.parse_expr();
-                
-                Statement::FieldWrite { base, field, value }
+                StatementKind::FieldWrite { base, field, value }
             }
 
             Token::Identifier(name) => {
                 let variable_name = name.clone();
                 self.tok.next();
 
-                match self.tok.next() {
-                    Token::Equals => {},
-                    other => panic!("Expected = in field write, got {:?}", other),
+                // a[i] = e
+                if self.tok.peek().get_type() == TokenType::LeftBracket {
+                    let variable = Expression::new(ExpressionKind::Variable(variable_name), start);
+                    let target = self.parse_indexing(variable)?;
+                    let kind = self.array_write(target)?;
+                    return Ok(Statement::new(kind, self.span_from(start)));
                 }
 
-                let expression = self.parse_expr();
+                // x: T = e
+                if self.tok.peek().get_type() == TokenType::Colon {
+                    self.tok.next();
+                    let typ = self.parse_type()?;
+                    self.expect(TokenType::Equals, "after declared type")?;
+                    let value = self.parse_expr()?;
+                    let kind = StatementKind::Declaration { name: variable_name, typ: Some(typ), value };
+                    return Ok(Statement::new(kind, self.span_from(start)));
+                }
+
+                self.expect(TokenType::Equals, "in assignment")?;
+
+                let expression = self.parse_expr()?;
 
                 if variable_name == "_" {
-                    Statement::Discard(expression)
+                    StatementKind::Discard(expression)
                 } else {
-                    Statement::Assignment { variable: variable_name, expression }
+                    StatementKind::Assignment { variable: variable_name, expression }
                 }
             }
-            
-            other => panic!("UNexpected token at start of statement: {:?}", other),
-        }
-    }
-
-    pub fn parse_method(&mut self) -> Method {
-        // method m(a, b, c, ...) with locals q, r, s, ...:
-        match self.tok.next() {
-            Token::Method => {},
-            other => panic!("Expected 'method', got {:?}", other),
-        };
 
-        let name = match self.tok.next() {
-            Token::Identifier(n) => n,
-            other => panic!("Expected method name, got {:?}", other),
+            _ => return Err(self.unexpected(vec![], "a statement")),
         };
 
-        match self.tok.next() {
-            Token::LeftParen => {},
-            other => panic!("expected '(' after method name, got {:?}", other),
-        }
+        Ok(Statement::new(kind, self.span_from(start)))
+    }
 
-        let mut args = Vec::<(String, Type)>::new();
-        while self.tok.peek().get_type() != TokenType::RightParen {
-            match self.tok.next() {
-                Token::Identifier(arg) => {
-                    match self.tok.next() {
-                        Token::Colon => {},
-                        other => panic!("Expected : after arg name, got {:?}", other),
-                    }
-                    let typ = self.parse_type();
-                    args.push((arg, typ));
-                },
-                other => panic!("Expected argument, got {:?}", other)
+    /*
+    whatever follows an if or elif block:
+        else { ... }
+        elif e: { ... }         (or else if e: { ... }) and then the same again
+    an elif is just an if nested in the else block, so the rest of the compiler never sees one.
+    a plain if has to have an else or elif, but a chain can stop after any elif, and that last
+    elif turns into an ifonly
+    */
+    fn parse_else(&mut self, required: bool) -> ParseResult<Vec<Statement>> {
+        let start = self.tok.peek_span();
+        match self.tok.peek().get_type() {
+            TokenType::Elif => {
+                self.tok.next();
             }
-
-            if self.tok.peek().get_type() == TokenType::Comma {
+            TokenType::Else => {
+                self.tok.next();
+                if self.tok.peek().get_type() != TokenType::If {
+                    return self.parse_block("else");
+                }
                 self.tok.next();
             }
+            _ if required => {
+                return Err(self.unexpected(vec![TokenType::Else, TokenType::Elif], "`else` or `elif` after if block"));
+            }
+            _ => return Ok(vec![]),
+        }
+
+        let condition = self.parse_expr()?;
+
+        self.expect(TokenType::Colon, "after elif condition")?;
+
+        let then_body = self.parse_block("elif")?;
+
+        let else_body = self.parse_else(false)?;
+
+        let kind = if else_body.is_empty() {
+            StatementKind::IfOnly { condition, body: then_body }
+        } else {
+            StatementKind::If { condition, then_body, else_body }
+        };
+        Ok(vec![Statement::new(kind, self.span_from(start))])
+    }
+
+    // name:type
+    fn parse_declaration(&mut self, what: &str) -> ParseResult<Declaration> {
+        let start = self.tok.peek_span();
+        let name = self.expect_identifier(what)?;
+        self.expect(TokenType::Colon, &format!("after {}", what))?;
+        let typ = self.parse_type()?;
+        Ok(Declaration { name, typ, span: self.span_from(start) })
+    }
+
+    // declarations separated by commas, up to (and including) the closing token
+    fn parse_declarations(&mut self, close: TokenType, what: &str) -> ParseResult<Vec<Declaration>> {
+        let mut declarations = Vec::<Declaration>::new();
+        while self.tok.peek().get_type() != close {
+            declarations.push(self.parse_declaration(what)?);
+            self.eat_comma();
         }
         self.tok.next();
+        Ok(declarations)
+    }
+
+    // method m(a, b, c, ...) returning T
+    fn parse_method_signature(&mut self) -> ParseResult<MethodSignature> {
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Method, "")?;
+        self.parse_signature_rest(start, "method")
+    }
+
+    // everything after the method or function keyword, what is which of the two for messages
+    fn parse_signature_rest(&mut self, start: Span, what: &str) -> ParseResult<MethodSignature> {
+        let name = self.expect_identifier(&format!("{} name", what))?;
+
+        self.expect(TokenType::LeftParen, &format!("after {} name", what))?;
+
+        let args = self.parse_declarations(TokenType::RightParen, "argument name")?;
 
         let return_type = if self.tok.peek().get_type() == TokenType::Returning {
             self.tok.next();
-            self.parse_type()
+            self.parse_type()?
         } else {
             Type::Int
         };
 
-        match self.tok.next() {
-            Token::With => {},
-            other => panic!("Expected 'with' after arguments, got {:?}", other),
-        }
+        Ok(MethodSignature { name, args, return_type, span: self.span_from(start) })
+    }
 
-        match self.tok.next() {
-            Token::Locals => {},
-            other => panic!("Expected 'locals' after 'with', got {:?}", other),
-        }
+    pub fn parse_method(&mut self) -> ParseResult<Method> {
+        // method m(a, b, c, ...) with locals q, r, s, ...:
+        let start = self.tok.peek_span();
+        let MethodSignature { name, args, return_type, .. } = self.parse_method_signature()?;
+        self.parse_method_rest(start, name, args, return_type)
+    }
 
-        let mut locals = Vec::<(String, Type)>::new();
-        while self.tok.peek().get_type() != TokenType::Colon {
-            match self.tok.next() {
-                Token::Identifier(local) => {
-                    match self.tok.next() {
-                        Token::Colon => {},
-                        other => panic!("Expected : after local name, got {:?}", other),
-                    }
-                    let typ = self.parse_type();
-                    locals.push((local, typ));
-                },
-                other => panic!("Expected local variable name, but got {:?}", other),
-            }
+    // function f(a, b, ...) returning T with locals q, r, ...:
+    // same as a method, just without a this
+    pub fn parse_function(&mut self) -> ParseResult<Method> {
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Function, "")?;
+        let MethodSignature { name, args, return_type, .. } = self.parse_signature_rest(start, "function")?;
+        self.parse_method_rest(start, name, args, return_type)
+    }
 
-            if self.tok.peek().get_type() == TokenType::Comma {
-                self.tok.next();
-            }
-        }
-        self.tok.next();
+    // constructor(a, b, ...) with locals q, r, ...:
+    // it is a method named constructor that returns int, the return value just gets thrown away
+    pub fn parse_constructor(&mut self) -> ParseResult<Method> {
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Constructor, "")?;
 
-        let mut body = Vec::<Statement>::new();
-        loop {
-            let peek_type = self.tok.peek().get_type();
-            if peek_type == TokenType::Method || peek_type == TokenType::RightBracket {
-                break;
-            }
-            body.push(self.parse_statement());
-        }
+        self.expect(TokenType::LeftParen, "after `constructor`")?;
+
+        let args = self.parse_declarations(TokenType::RightParen, "argument name")?;
+
+        self.parse_method_rest(start, "constructor".to_string(), args, Type::Int)
+    }
+
+    // the with locals ...: and the body, shared by methods and constructors
+    fn parse_method_rest(&mut self, start: Span, name: String, args: Vec<Declaration>, return_type: Type) -> ParseResult<Method> {
+        self.expect(TokenType::With, "after arguments")?;
+
+        self.expect(TokenType::Locals, "after 'with'")?;
 
-        Method { name, args, locals, body, return_type }
+        let locals = self.parse_declarations(TokenType::Colon, "local variable name")?;
+
+        let body = self.parse_statements();
+
+        Ok(Method { name, args, locals, body, return_type, span: self.span_from(start) })
     }
 
-    pub fn parse_class(&mut self) -> Class {
+    pub fn parse_class(&mut self) -> ParseResult<Class> {
         /*
-        class NAME [
+        class NAME [                  or   class NAME<T, U> extends SUPER implements I1, I2 [
             fields x, y, z, ....
+            constructor(a, b, ...) with locals q, ...:       (optional, at most one)
+                <one or more statements>
             method m(a, b, c, ...) with locals q, r, s, ...:
                 <one or more statements>
             method m2(...) with locals ...:
                 <one or more statements>
         ]
         */
-        match self.tok.next() {
-            Token::Class => {},
-            other => panic!("Expected 'class', got {:?}", other),
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Class, "")?;
 
+        let name = self.expect_identifier("class name")?;
+
+        // class Pair<A, B>
+        let mut type_params = vec![];
+        if self.at_type_args() {
+            self.tok.next();
+            loop {
+                type_params.push(self.expect_identifier("type parameter name")?);
+                if self.tok.peek().get_type() != TokenType::Comma {
+                    break;
+                }
+                self.tok.next();
+            }
+            self.close_angle("after type parameters")?;
         }
 
-        let name = match self.tok.next() {
-            Token::Identifier(name) => name,
-            other => panic!("Expected class name, got {:?}", other),
+        let superclass = if self.tok.peek().get_type() == TokenType::Extends {
+            self.tok.next();
+            let superclass_start = self.tok.peek_span();
+            let superclass = self.expect_identifier("superclass name after `extends`")?;
+            Some((superclass, self.span_from(superclass_start)))
+        } else {
+            None
         };
 
-        match self.tok.next() {
-            Token::LeftBracket => {},
-            other => panic!("Expected '[', got {:?}", other),
+        let mut interfaces = vec![];
+        if self.tok.peek().get_type() == TokenType::Implements {
+            self.tok.next();
+            loop {
+                let interface_start = self.tok.peek_span();
+                let interface = self.expect_identifier("interface name after `implements`")?;
+                interfaces.push((interface, self.span_from(interface_start)));
+                if self.tok.peek().get_type() != TokenType::Comma {
+                    break;
+                }
+                self.tok.next();
+            }
         }
 
-        match self.tok.next() {
-            Token::Fields => {},
-            other => panic!("Expected 'fields, got {:?}", other),
-        }
+        self.expect(TokenType::LeftBracket, "after class name")?;
 
-        let mut fields = Vec::<(String, Type)>::new();
-        while self.tok.peek().get_type() != TokenType::Method && self.tok.peek().get_type() != TokenType::RightBracket {
-            match self.tok.next() {
-                Token::Identifier(field) => {
-                    match self.tok.next() {
-                        Token::Colon => {},
-                        other => panic!("Expected : after field name, got {:?}", other),
-                    }
-                    let typ = self.parse_type();
-                    fields.push((field, typ));
-                },
-                other => panic!("Expected a field name, got {:?}", other),
-            }
+        self.expect(TokenType::Fields, "at start of class body")?;
 
-            if self.tok.peek().get_type() == TokenType::Comma {
-                self.tok.next();
+        let mut fields = Vec::<Declaration>::new();
+        while !matches!(self.tok.peek().get_type(), TokenType::Method | TokenType::Constructor | TokenType::RightBracket) {
+            match self.parse_declaration("field name") {
+                Ok(field) => fields.push(field),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_member();
+                    break;
+                }
             }
+            self.eat_comma();
         }
 
         let mut methods = Vec::<Method>::new();
+        let mut constructor = None;
+        loop {
+            let parsed = match self.tok.peek().get_type() {
+                TokenType::Method => self.parse_method().map(|method| methods.push(method)),
+                TokenType::Constructor if constructor.is_some() => {
+                    let error = self.unexpected(vec![TokenType::Method, TokenType::RightBracket],
+                        "`method` or `]` (a class can only have one constructor)");
+                    self.skip_token();
+                    Err(error)
+                }
+                TokenType::Constructor => self.parse_constructor().map(|method| constructor = Some(method)),
+                _ => break,
+            };
+            if let Err(error) = parsed {
+                self.errors.push(error);
+                self.synchronize_member();
+            }
+        }
+
+        self.expect(TokenType::RightBracket, "at end of class")?;
+
+        Ok(Class { name, type_params, superclass, interfaces, fields, constructor, methods, span: self.span_from(start) })
+    }
+
+    pub fn parse_interface(&mut self) -> ParseResult<Interface> {
+        /*
+        interface NAME [
+            method m(a, b, ...) returning T
+            method m2(...) returning T
+        ]
+        */
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Interface, "")?;
+
+        let name = self.expect_identifier("interface name")?;
+
+        self.expect(TokenType::LeftBracket, "after interface name")?;
+
+        let mut methods = Vec::<MethodSignature>::new();
         while self.tok.peek().get_type() == TokenType::Method {
-            methods.push(self.parse_method());
+            match self.parse_method_signature() {
+                Ok(method) => methods.push(method),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_member();
+                }
+            }
+        }
+
+        self.expect(TokenType::RightBracket, "at end of interface")?;
+
+        Ok(Interface { name, methods, span: self.span_from(start) })
+    }
+
+    pub fn parse_enum(&mut self) -> ParseResult<Enum> {
+        /*
+        enum NAME [
+            Variant(a:int, b:A)
+            OtherVariant
+        ]
+        */
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Enum, "")?;
+
+        let name = self.expect_identifier("enum name")?;
+
+        self.expect(TokenType::LeftBracket, "after enum name")?;
+
+        let mut variants = Vec::<Variant>::new();
+        while self.tok.peek().get_type() != TokenType::RightBracket {
+            let variant_start = self.tok.peek_span();
+            let variant = self.expect_identifier("variant name")?;
+            let fields = if self.tok.peek().get_type() == TokenType::LeftParen {
+                self.tok.next();
+                self.parse_declarations(TokenType::RightParen, "field name")?
+            } else {
+                vec![]
+            };
+            variants.push(Variant { name: variant, fields, span: self.span_from(variant_start) });
+            self.eat_comma();
+        }
+        // with no variants there could never be a value of it
+        if variants.is_empty() {
+            return Err(self.unexpected(vec![TokenType::Identifier], "at least one variant"));
         }
 
-        match self.tok.next() {
-            Token::RightBracket => {},
-            other => panic!("Expected ']' at end of class, got {:?}", other),
+        self.expect(TokenType::RightBracket, "at end of enum")?;
+
+        Ok(Enum { name, variants, span: self.span_from(start) })
+    }
+
+    // import "lib/list.441"
+    fn parse_import(&mut self) -> ParseResult<Import> {
+        let start = self.tok.peek_span();
+        self.expect(TokenType::Import, "")?;
+        match self.tok.peek() {
+            Token::StringLiteral(_) => match self.tok.next() {
+                Token::StringLiteral(path) => Ok(Import { path, span: self.span_from(start) }),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected(vec![TokenType::StringLiteral], "file path after `import`")),
         }
-        
-        Class { name, fields, methods }
     }
 
-    pub fn parse_program(&mut self) -> Program {
+    // returns every syntax error in the file if there were any
+    // root is false for a file pulled in by an import, which is only declarations and has no main
+    pub fn parse_program(&mut self, root: bool) -> Result<Program, Vec<ParseError>> {
+        let mut imports = Vec::<Import>::new();
         let mut classes = Vec::<Class>::new();
+        let mut interfaces = Vec::<Interface>::new();
+        let mut enums = Vec::<Enum>::new();
+        let mut functions = Vec::<Method>::new();
 
-        while self.tok.peek().get_type() == TokenType::Class {
-            classes.push(self.parse_class());
+        loop {
+            let parsed = match self.tok.peek().get_type() {
+                TokenType::Import => self.parse_import().map(|import| imports.push(import)),
+                TokenType::Class => self.parse_class().map(|class| classes.push(class)),
+                TokenType::Interface => self.parse_interface().map(|interface| interfaces.push(interface)),
+                TokenType::Enum => self.parse_enum().map(|declared| enums.push(declared)),
+                TokenType::Function => self.parse_function().map(|function| functions.push(function)),
+                // in the root file anything else has to be main, which parse_main_header complains about
+                TokenType::Main | TokenType::Eof => break,
+                _ if root => break,
+                _ => {
+                    let error = self.unexpected(
+                        vec![TokenType::Class, TokenType::Interface, TokenType::Enum, TokenType::Function, TokenType::Import],
+                        "a class, interface, enum, function, or import");
+                    self.errors.push(error);
+                    // step over it first so synchronizing cant leave us stuck on it
+                    self.skip_token();
+                    self.synchronize_class();
+                    Ok(())
+                }
+            };
+            if let Err(error) = parsed {
+                self.errors.push(error);
+                self.synchronize_class();
+            }
         }
 
-        match self.tok.next() {
-            Token::Main => {},
-            other => panic!("Expected 'main' but got {:?}", other),
+        // an imported file stops here, main can only be in the file being compiled
+        if !root {
+            if self.tok.peek().get_type() == TokenType::Eof {
+                if !self.errors.is_empty() {
+                    return Err(std::mem::take(&mut self.errors));
+                }
+                return Ok(Program { imports, classes, interfaces, enums, functions, main_locals: vec![], main_body: vec![] });
+            }
+            // keep parsing it anyway so its own syntax errors get reported too
+            let error = self.unexpected(vec![], "only declarations in an imported file");
+            self.errors.push(error);
         }
 
-        match self.tok.next() {
-            Token::With => {},
-            other => panic!("Expected 'with', but got {:?}", other),
+        let mut main_locals = Vec::<Declaration>::new();
+        let mut main_body = Vec::<Statement>::new();
+        match self.parse_main_header() {
+            Ok(locals) => main_locals = locals,
+            Err(error) => {
+                let line = error.span.line;
+                self.errors.push(error);
+                self.synchronize_statement(line);
+            }
         }
 
-        let mut main_locals = Vec::<(String,Type)>::new();
-        while self.tok.peek().get_type() != TokenType::Colon {
-            match self.tok.next() {
-                Token::Identifier(local) => {
-                    match self.tok.next() {
-                        Token::Colon => {},
-                        other => panic!("Expected : after local name, got {:?}", other),
-                    }
-                    let typ = self.parse_type();
-                    main_locals.push((local, typ));
-                },
-                other => panic!("(Expected local variable name but got {:?}", other),
+        // main runs to the end of the file, so anything that stopped the statements early is stray
+        loop {
+            main_body.extend(self.parse_statements());
+            if self.tok.peek().get_type() == TokenType::Eof {
+                break;
             }
+            let error = self.unexpected(vec![], "a statement");
+            self.errors.push(error);
+            self.skip_token();
+        }
 
-            if self.tok.peek().get_type() == TokenType::Comma {
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+
+        Ok(Program { imports, classes, interfaces, enums, functions, main_locals, main_body })
+    }
+
+    // main with x:int, y:A:
+    fn parse_main_header(&mut self) -> ParseResult<Vec<Declaration>> {
+        self.expect(TokenType::Main, "after classes, interfaces, and functions")?;
+        self.expect(TokenType::With, "after 'main'")?;
+        self.parse_declarations(TokenType::Colon, "local variable name")
+    }
+
+    // int, bool, str, A, A?, Stack<int>, or any of those followed by [] any number of times
+    // or a function type, (int, A) -> bool
+    pub fn parse_type(&mut self) -> ParseResult<Type> {
+        if self.tok.peek().get_type() == TokenType::LeftParen {
+            return self.parse_function_type();
+        }
+        let name = self.expect_identifier("type")?;
+        let mut typ = self.parse_named_type(name)?;
+        while self.tok.peek().get_type() == TokenType::LeftBracket {
+            self.tok.next();
+            self.expect(TokenType::RightBracket, "in array type")?;
+            typ = Type::Array(Box::new(typ));
+            // int[]? is an array that might be null, A?[] is an array of things that might be
+            if self.tok.peek().get_type() == TokenType::Question {
                 self.tok.next();
+                typ = Type::Nullable(Box::new(typ));
             }
         }
+        Ok(typ)
+    }
+
+    // (int, A) -> bool, the return type takes any [] after it, so (int) -> int[] returns an int[]
+    fn parse_function_type(&mut self) -> ParseResult<Type> {
+        self.tok.next();
+        let mut args = vec![];
+        while self.tok.peek().get_type() != TokenType::RightParen {
+            args.push(self.parse_type()?);
+            self.eat_comma();
+        }
         self.tok.next();
+        self.expect(TokenType::Arrow, "after argument types of a function type")?;
+        let ret = self.parse_type()?;
+        Ok(Type::Function(args, Box::new(ret)))
+    }
 
-        let mut main_body = Vec::<Statement>::new();
-        while self.tok.peek().get_type() != TokenType::Eof {
-            main_body.push(self.parse_statement());
+    // the rest of a type after its name, type arguments and then a ? if it can be null, Stack<int>?
+    fn parse_named_type(&mut self, name: String) -> ParseResult<Type> {
+        let mut typ = if self.at_type_args() {
+            Type::Generic(name, self.parse_type_args()?)
+        } else {
+            named_type(name)
+        };
+        if self.tok.peek().get_type() == TokenType::Question {
+            self.tok.next();
+            typ = Type::Nullable(Box::new(typ));
         }
-        
-        Program { classes, main_locals, main_body }
+        Ok(typ)
     }
 
-    pub fn parse_type(&mut self) -> Type {
-        match self.tok.next() {
-            Token::Identifier(name) => {
-                if name == "int" {
-                    Type::Int
-                } else {
-                    Type::ClassType(name)
-                }
-            }
-            other => panic!("Expected type, not {:?}", other)
+    // a < straight after a class name always starts type arguments, comparing against @A would never typecheck
+    fn at_type_args(&mut self) -> bool {
+        matches!(self.tok.peek(), Token::Operator(Operator::LessThan))
+    }
+
+    // <int, Stack<str>>, starting at the <
+    fn parse_type_args(&mut self) -> ParseResult<Vec<Type>> {
+        self.tok.next();
+        let mut args = vec![self.parse_type()?];
+        while self.tok.peek().get_type() == TokenType::Comma {
+            self.tok.next();
+            args.push(self.parse_type()?);
+        }
+        self.close_angle("to close type arguments")?;
+        Ok(args)
+    }
+
+    fn close_angle(&mut self, context: &str) -> ParseResult<()> {
+        self.tok.split_shift_right();
+        if matches!(self.tok.peek(), Token::Operator(Operator::GreaterThan)) {
+            self.tok.next();
+            Ok(())
+        } else {
+            Err(self.unexpected(vec![TokenType::Operator], &format!("`>` {}", context)))
         }
     }
-}
\ No newline at end of file
+}
+
+// the builtin types are plain identifiers as far as the tokenizer is concerned
+fn named_type(name: String) -> Type {
+    match name.as_str() {
+        "int" => Type::Int,
+        "bool" => Type::Bool,
+        "str" => Type::Str,
+        _ => Type::ClassType(name),
+    }
+}
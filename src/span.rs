@@ -0,0 +1,41 @@
+/*
+A span is a location in the source text
+
+start and end are byte offsets (end is exclusive) so we can slice the source back out,
+and line/col are 1-based and point at the start of the span so we can tell the user
+where things are without having to rescan the file.
+file is the index of the source file the span is in, the root file is 0 and every import gets the next one
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(file: usize, start: usize, end: usize, line: usize, col: usize) -> Self {
+        Span { file, start, end, line, col }
+    }
+
+    // covers everything from the start of self to the end of other
+    // used by the parser to build a node's span out of its first and last tokens
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            col: self.col,
+        }
+    }
+}
+
+// line:col, which is what goes into error messages
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
@@ -1,7 +1,40 @@
+use crate::ast::Type;
 use crate::expression::Expression;
+use crate::span::Span;
 
+// Circle(r): { ... } in a match, variant is None for the _ arm that takes everything left
+// a binding of _ skips that field
 #[derive(Debug, Clone)]
-pub enum Statement {
+pub struct MatchArm {
+    pub variant: Option<String>,
+    pub bindings: Vec<String>,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+// catch (e: C) { ... } after a try, runs when the thrown object is a C (or a subclass of C) with it bound to name
+#[derive(Debug, Clone)]
+pub struct Catch {
+    pub name: String,
+    pub typ: Type,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
     
     // x = e for any variable x and expression e
     Assignment {
@@ -9,6 +42,14 @@ pub enum Statement {
         expression: Expression
     },
 
+    // var x = e or x: T = e, declares x from here to the end of the enclosing block.
+    // typ is None for var, then x gets whatever type e has
+    Declaration {
+        name: String,
+        typ: Option<Type>,
+        value: Expression
+    },
+
     // _ = e for any e. This is used if you don’t care about the expression’s result, 
     // but are just running it for side effects (e.g., printing from a method called in e)
     Discard(Expression),
@@ -20,7 +61,15 @@ pub enum Statement {
         value: Expression
     },
 
+    // a[i] = e, or !e[i] = e when the array isnt just a variable
+    ArrayWrite {
+        array: Expression,
+        index: Expression,
+        value: Expression
+    },
+
     // if e: { <newline> <one or more statements> } else { <newline> <one or more statements> }
+    // elif e: { ... } and else if e: { ... } chains are parsed into an If (or IfOnly) as the else_body
     If {
         condition: Expression,
         then_body: Vec<Statement>,
@@ -39,6 +88,39 @@ pub enum Statement {
         body: Vec<Statement>
     },
 
+    // for i = a to b: { <newline> <one or more statements> }
+    // i counts up from a to b, both included. b is only evaluated once, before the first pass
+    For {
+        variable: String,
+        from: Expression,
+        to: Expression,
+        body: Vec<Statement>
+    },
+
+    // match e: { Circle(r): { ... } Rect(w, h): { ... } _: { ... } }
+    // runs the arm for e's variant with its fields bound, the arms have to cover every variant
+    Match {
+        value: Expression,
+        arms: Vec<MatchArm>,
+    },
+
+    // throw e, e has to be an object of some class
+    // control goes to the nearest enclosing catch that takes its class, in this method or any caller
+    Throw(Expression),
+
+    // try { ... } catch (e: A) { ... } catch (e: B) { ... }
+    // the first catch whose class the thrown object has wins, anything none of them take keeps going up
+    Try {
+        body: Vec<Statement>,
+        catches: Vec<Catch>,
+    },
+
+    // leave the innermost loop
+    Break,
+
+    // skip to the next pass of the innermost loop
+    Continue,
+
     // return e
     Return(Expression),
 
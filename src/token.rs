@@ -1,12 +1,21 @@
+use crate::span::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Top Level
     Class,
     Fields,
     Method,
+    Constructor,
     Locals,
     Main,
     With,
+    Extends,
+    Interface,
+    Implements,
+    Function,
+    Import,
+    Enum,
 
     // Smybols
     LeftParen,
@@ -17,8 +26,12 @@ pub enum TokenType {
     Ampersand,
     AtSign,
     Not,
+    Tilde,
     Dot,
     Colon,
+    // A? is a nullable A
+    Question,
+    Arrow,
     Comma,
     LeftBracket,
     RightBracket,
@@ -27,17 +40,32 @@ pub enum TokenType {
     This,
     If,
     Else,
+    Elif,
     IfOnly,
     While,
+    For,
+    To,
+    Break,
+    Continue,
+    Match,
+    Throw,
+    Try,
+    Catch,
+    Var,
     Return,
     Print,
+    Len,
     Eof,
     Operator,
     Number,
+    StringLiteral,
     Identifier,
     Equals,
     Returning,
     Null,
+    True,
+    False,
+    Error,
 }
 
 #[derive(Debug, Clone)]
@@ -45,12 +73,21 @@ pub enum Token {
     Class,
     Fields,
     Method,
+    Constructor,
     Locals,
     Main,
     With,
+    Extends,
+    Interface,
+    Implements,
+    Function,
+    Import,
+    Enum,
 
     // number accept i64 num of course
     Number(i64),
+    // "text" with the escapes already turned into the characters they stand for
+    StringLiteral(String),
     // operator will accept an operator character like + - / *
     Operator(Operator),
 
@@ -64,23 +101,50 @@ pub enum Token {
     Ampersand,
     AtSign,
     Not,
+    Tilde,
     Dot,
     LeftBracket,
     RightBracket,
 
     If,
     Else,
+    Elif,
     IfOnly,
     While,
+    For,
+    To,
+    Break,
+    Continue,
+    Match,
+    Throw,
+    Try,
+    Catch,
+    Var,
     Return,
     Print,
+    Len,
     Colon,
+    Question,
+    Arrow,
     Comma,
     Eof,
     This,
     Equals,
     Returning,
     Null,
+    True,
+    False,
+
+    // something the tokenizer couldnt make sense of, like an unsupported character
+    // or an unterminated comment. the parser reports the message instead of the tokenizer panicking
+    Error(String),
+}
+
+// a token along with where it came from in the source
+#[derive(Debug, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -92,10 +156,60 @@ pub enum Operator {
     Equals,
     LessThan,
     GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    Modulo,
+    ShiftLeft,
+    ShiftRight,
     BitwiseAnd,
     BitwiseOr,
     BitwiseXor,
     NotEquals,
+    // short circuit, the right side only runs when the left side doesnt decide the answer
+    And,
+    Or,
+}
+
+// operators that go in front of a single operand
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnaryOperator {
+    // -x
+    Negate,
+    // !x, flips a bool
+    Not,
+    // ~x, flips every bit
+    Complement,
+}
+
+impl std::fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "!"),
+            UnaryOperator::Complement => write!(f, "~"),
+        }
+    }
+}
+
+impl Operator {
+    // how tightly the operator binds, higher binds tighter
+    // same order as c: or < and < | < ^ < & < equality < comparison < shifts < + - < * / %
+    // every level is left associative
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::BitwiseOr => 3,
+            Operator::BitwiseXor => 4,
+            Operator::BitwiseAnd => 5,
+            Operator::Equals | Operator::NotEquals => 6,
+            Operator::LessThan | Operator::GreaterThan
+            | Operator::LessEqual | Operator::GreaterEqual => 7,
+            Operator::ShiftLeft | Operator::ShiftRight => 8,
+            Operator::Plus | Operator::Minus => 9,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 10,
+        }
+    }
 }
 
 impl std::fmt::Display for Operator {
@@ -108,6 +222,13 @@ impl std::fmt::Display for Operator {
             Operator::Equals => write!(f, "=="),
             Operator::LessThan => write!(f, "<"),
             Operator::GreaterThan => write!(f, ">"),
+            Operator::LessEqual => write!(f, "<="),
+            Operator::GreaterEqual => write!(f, ">="),
+            Operator::Modulo => write!(f, "%"),
+            Operator::ShiftLeft => write!(f, "<<"),
+            Operator::ShiftRight => write!(f, ">>"),
+            Operator::And => write!(f, "and"),
+            Operator::Or => write!(f, "or"),
             Operator::BitwiseAnd => write!(f, "&"),
             Operator::BitwiseOr => write!(f, "|"),
             Operator::BitwiseXor => write!(f, "^"),
@@ -120,6 +241,7 @@ impl Token {
     pub fn get_type(&self) -> TokenType {
         match self {
             Token::Number(_) => TokenType::Number,
+            Token::StringLiteral(_) => TokenType::StringLiteral,
             Token::Operator(_) => TokenType::Operator,
             Token::Identifier(_) => TokenType::Identifier,
             Token::LeftParen => TokenType::LeftParen,
@@ -130,15 +252,29 @@ impl Token {
             Token::Ampersand => TokenType::Ampersand,
             Token::AtSign => TokenType::AtSign,
             Token::Not => TokenType::Not,
+            Token::Tilde => TokenType::Tilde,
             Token::Dot => TokenType::Dot,
             Token::If => TokenType::If,
             Token::Else => TokenType::Else,
+            Token::Elif => TokenType::Elif,
             Token::IfOnly => TokenType::IfOnly,
             Token::While => TokenType::While,
+            Token::For => TokenType::For,
+            Token::To => TokenType::To,
+            Token::Break => TokenType::Break,
+            Token::Continue => TokenType::Continue,
+            Token::Match => TokenType::Match,
+            Token::Throw => TokenType::Throw,
+            Token::Try => TokenType::Try,
+            Token::Catch => TokenType::Catch,
+            Token::Var => TokenType::Var,
             Token::Return => TokenType::Return,
             Token::Returning => TokenType::Returning,
             Token::Print => TokenType::Print,
+            Token::Len => TokenType::Len,
             Token::Colon => TokenType::Colon,
+            Token::Question => TokenType::Question,
+            Token::Arrow => TokenType::Arrow,
             Token::Comma => TokenType::Comma,
             Token::Eof => TokenType::Eof,
             Token::This => TokenType::This,
@@ -146,12 +282,103 @@ impl Token {
             Token::Class => TokenType::Class,
             Token::Fields => TokenType::Fields,
             Token::Method => TokenType::Method,
+            Token::Constructor => TokenType::Constructor,
             Token::With => TokenType::With,
+            Token::Extends => TokenType::Extends,
+            Token::Interface => TokenType::Interface,
+            Token::Implements => TokenType::Implements,
+            Token::Function => TokenType::Function,
+            Token::Import => TokenType::Import,
+            Token::Enum => TokenType::Enum,
             Token::Locals => TokenType::Locals,
             Token::Main => TokenType::Main,
             Token::LeftBracket => TokenType::LeftBracket,
             Token::RightBracket => TokenType::RightBracket,
             Token::Null => TokenType::Null,
+            Token::True => TokenType::True,
+            Token::False => TokenType::False,
+            Token::Error(_) => TokenType::Error,
         }
     }
-}
\ No newline at end of file
+}
+
+// how a token type reads in an error message, ex: expected `:` after if condition
+impl std::fmt::Display for TokenType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            TokenType::Class => "`class`",
+            TokenType::Fields => "`fields`",
+            TokenType::Method => "`method`",
+            TokenType::Constructor => "`constructor`",
+            TokenType::Locals => "`locals`",
+            TokenType::Main => "`main`",
+            TokenType::With => "`with`",
+            TokenType::Extends => "`extends`",
+            TokenType::Interface => "`interface`",
+            TokenType::Implements => "`implements`",
+            TokenType::Function => "`function`",
+            TokenType::Import => "`import`",
+            TokenType::Enum => "`enum`",
+            TokenType::LeftParen => "`(`",
+            TokenType::RightParen => "`)`",
+            TokenType::LeftBrace => "`{`",
+            TokenType::RightBrace => "`}`",
+            TokenType::Caret => "`^`",
+            TokenType::Ampersand => "`&`",
+            TokenType::AtSign => "`@`",
+            TokenType::Not => "`!`",
+            TokenType::Tilde => "`~`",
+            TokenType::Dot => "`.`",
+            TokenType::Colon => "`:`",
+            TokenType::Question => "`?`",
+            TokenType::Arrow => "`->`",
+            TokenType::Comma => "`,`",
+            TokenType::LeftBracket => "`[`",
+            TokenType::RightBracket => "`]`",
+            TokenType::This => "`this`",
+            TokenType::If => "`if`",
+            TokenType::Else => "`else`",
+            TokenType::Elif => "`elif`",
+            TokenType::IfOnly => "`ifonly`",
+            TokenType::While => "`while`",
+            TokenType::For => "`for`",
+            TokenType::To => "`to`",
+            TokenType::Break => "`break`",
+            TokenType::Continue => "`continue`",
+            TokenType::Match => "`match`",
+            TokenType::Throw => "`throw`",
+            TokenType::Try => "`try`",
+            TokenType::Catch => "`catch`",
+            TokenType::Var => "`var`",
+            TokenType::Return => "`return`",
+            TokenType::Print => "`print`",
+            TokenType::Len => "`len`",
+            TokenType::Eof => "end of file",
+            TokenType::Operator => "an operator",
+            TokenType::Number => "a number",
+            TokenType::StringLiteral => "a string",
+            TokenType::Identifier => "an identifier",
+            TokenType::Equals => "`=`",
+            TokenType::Returning => "`returning`",
+            TokenType::Null => "`null`",
+            TokenType::True => "`true`",
+            TokenType::False => "`false`",
+            TokenType::Error => "an invalid token",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// same as the token type, but with the actual text for tokens that carry a value
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::StringLiteral(text) => write!(f, "`{:?}`", text),
+            Token::Operator(op) => write!(f, "`{}`", op),
+            Token::Identifier(name) => write!(f, "`{}`", name),
+            Token::Error(message) => write!(f, "{}", message),
+            other => write!(f, "{}", other.get_type()),
+        }
+    }
+}
@@ -1,18 +1,38 @@
-use crate::token::{Token, Operator};
+use crate::span::Span;
+use crate::token::{SpannedToken, Token, Operator};
 
 pub struct Tokenizer {
     text: String,
+    // which source file this is, stamped on every span
+    file: usize,
     current: usize,
-    cached: Option<Token>,
+    cached: Option<SpannedToken>,
+
+    // byte offset of the first character of every line
+    // lets us turn a byte offset into line/col with a binary search
+    line_starts: Vec<usize>,
+
+    // span of the last token handed out by next()
+    previous: Span,
 }
 
 impl Tokenizer {
-    // takes text string
-    pub fn new(text: String) -> Self {
+    // takes text string and the index of the file it came from
+    pub fn new(text: String, file: usize) -> Self {
+        let mut line_starts = vec![0];
+        for (i, b) in text.bytes().enumerate() {
+            if b == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+
         Tokenizer {
             text,
+            file,
             current: 0,
             cached: None,
+            line_starts,
+            previous: Span::new(file, 0, 0, 1, 1),
         }
     }
 
@@ -21,20 +41,68 @@ impl Tokenizer {
         if self.cached.is_none() {
             self.cached = Some(self.advance_current());
         }
-        return self.cached.as_ref().unwrap();
+        &self.cached.as_ref().unwrap().token
+    }
+
+    // span of the token peek() would return
+    pub fn peek_span(&mut self) -> Span {
+        self.peek();
+        self.cached.as_ref().unwrap().span
     }
 
     // take token from cached
     // cached will be None after called
     pub fn next(&mut self) -> Token {
-        if let Some(token) = self.cached.take() {
-            token
-        } else {
-            self.advance_current()
+        let spanned = match self.cached.take() {
+            Some(spanned) => spanned,
+            None => self.advance_current(),
+        };
+        self.previous = spanned.span;
+        spanned.token
+    }
+
+    // Stack<Stack<int>> ends in >>, which reads as a shift everywhere else
+    // when the parser wants a single > it splits the peeked >> in two and takes the first half
+    pub fn split_shift_right(&mut self) {
+        self.peek();
+        let span = self.cached.as_ref().unwrap().span;
+        if matches!(self.cached.as_ref().unwrap().token, Token::Operator(Operator::ShiftRight)) {
+            self.cached = Some(SpannedToken {
+                token: Token::Operator(Operator::GreaterThan),
+                span: self.span_at(span.start, span.start + 1),
+            });
+            // the second > gets scanned again on the next peek
+            self.current = span.start + 1;
         }
     }
 
-    fn advance_current(&mut self) -> Token {
+    // span of the last token returned by next()
+    pub fn previous_span(&self) -> Span {
+        self.previous
+    }
+
+    fn span_at(&self, start: usize, end: usize) -> Span {
+        // index of the last line that starts at or before start
+        let line = match self.line_starts.binary_search(&start) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        Span::new(self.file, start, end, line + 1, start - self.line_starts[line] + 1)
+    }
+
+    fn advance_current(&mut self) -> SpannedToken {
+        if let Some(error) = self.skip_whitespace() {
+            return error;
+        }
+
+        let start = self.current;
+        let token = self.scan_token();
+        SpannedToken { token, span: self.span_at(start, self.current) }
+    }
+
+    // skips whitespace and comments
+    // returns an error token if a block comment never gets closed
+    fn skip_whitespace(&mut self) -> Option<SpannedToken> {
         while self.current < self.text.len() {
             // since rust uses variable width encoding, we can do byte indexing here 
             // for O(1) opetation
@@ -56,9 +124,134 @@ impl Tokenizer {
                 continue;
             }
 
+            if self.text[self.current..].starts_with("/*") {
+                let start = self.current;
+                if !self.skip_block_comment() {
+                    return Some(SpannedToken {
+                        token: Token::Error("unterminated block comment".to_string()),
+                        span: self.span_at(start, self.current),
+                    });
+                }
+                continue;
+            }
+
             break;
         }
+        None
+    }
+
+    // /* ... */ comments nest, so /* a /* b */ c */ is one comment
+    // returns false if we hit the end of the file before the comment closed
+    fn skip_block_comment(&mut self) -> bool {
+        let bytes = self.text.as_bytes();
+        let mut depth = 0;
+        while self.current < bytes.len() {
+            if bytes[self.current] == b'/' && bytes.get(self.current + 1) == Some(&b'*') {
+                depth += 1;
+                self.current += 2;
+            } else if bytes[self.current] == b'*' && bytes.get(self.current + 1) == Some(&b'/') {
+                depth -= 1;
+                self.current += 2;
+                if depth == 0 {
+                    return true;
+                }
+            } else {
+                self.current += 1;
+            }
+        }
+        false
+    }
 
+    /*
+    number literals:
+        123         decimal
+        0x1F        hex
+        0b1010      binary
+        1_000_000   _ can go anywhere after the first digit to break up long numbers
+
+    hex and binary literals can use all 64 bits, so 0xFFFFFFFFFFFFFFFF is allowed and just wraps to -1.
+    the ir's ints are 64 bit anyway and that makes masks easy to write
+    */
+    fn scan_number(&mut self) -> Token {
+        let bytes = self.text.as_bytes();
+        let radix = match (bytes[self.current], bytes.get(self.current + 1)) {
+            (b'0', Some(b'x' | b'X')) => 16,
+            (b'0', Some(b'b' | b'B')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.current += 2;
+        }
+
+        let start = self.current;
+        // This while look will allows us to tokenize digits of any length
+        while self.current < self.text.len() {
+            let next_ch = bytes[self.current] as char;
+            if !next_ch.is_digit(radix) && next_ch != '_' {
+                break;
+            }
+            self.current += 1;
+        }
+        let digits: String = self.text[start..self.current].chars().filter(|c| *c != '_').collect();
+
+        // something like 0x without digits, or 0b12 where the 2 would start a new number
+        if digits.is_empty() {
+            return Token::Error(format!("missing digits after '{}'", &self.text[start - 2..start]));
+        }
+        if self.current < self.text.len() && (bytes[self.current] as char).is_ascii_alphanumeric() {
+            let ch = bytes[self.current] as char;
+            return Token::Error(format!("invalid digit '{}' in number literal", ch));
+        }
+
+        let value = if radix == 10 {
+            digits.parse::<i64>().ok()
+        } else {
+            u64::from_str_radix(&digits, radix).ok().map(|v| v as i64)
+        };
+        match value {
+            Some(value) => Token::Number(value),
+            None => Token::Error(format!("number literal {} is too large", &self.text[start..self.current])),
+        }
+    }
+
+    /*
+    "hello\n", a string literal on a single line
+    the escapes are \n \t \0 \" and \\, anything else after a backslash is an error.
+    a bad escape still scans on to the closing quote, so the rest of the literal isnt read as code
+    */
+    fn scan_string(&mut self) -> Token {
+        // step over the opening quote
+        self.current += 1;
+        let mut value = String::new();
+        let mut bad_escape = None;
+        let mut chars = self.text[self.current..].chars();
+        while let Some(ch) = chars.next() {
+            self.current += ch.len_utf8();
+            match ch {
+                '"' => return match bad_escape {
+                    Some(escaped) => Token::Error(format!("unknown escape '\\{}' in string literal", escaped)),
+                    None => Token::StringLiteral(value),
+                },
+                '\n' => break,
+                '\\' => {
+                    let Some(escaped) = chars.next() else { break };
+                    self.current += escaped.len_utf8();
+                    match escaped {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        '0' => value.push('\0'),
+                        '"' => value.push('"'),
+                        '\\' => value.push('\\'),
+                        other => bad_escape = bad_escape.or(Some(other)),
+                    }
+                }
+                other => value.push(other),
+            }
+        }
+        Token::Error("unterminated string literal".to_string())
+    }
+
+    fn scan_token(&mut self) -> Token {
         if self.current >= self.text.len() {
             return Token::Eof;
         }
@@ -71,6 +264,7 @@ impl Tokenizer {
             '{' => { self.current += 1; Token::LeftBrace }
             '}' => { self.current += 1; Token::RightBrace }
             ':' => { self.current += 1; Token::Colon }
+            '?' => { self.current += 1; Token::Question }
             
             '!' => {
                 self.current += 1;
@@ -85,6 +279,7 @@ impl Tokenizer {
             }
             
             '@' => { self.current += 1; Token::AtSign }
+            '~' => { self.current += 1; Token::Tilde }
             '^' => { self.current += 1; Token::Caret }
             '&' => { self.current += 1; Token::Ampersand }
             '.' => { self.current += 1; Token::Dot }
@@ -93,11 +288,34 @@ impl Tokenizer {
             ']' => { self.current += 1; Token::RightBracket }
                     
             '+' => { self.current += 1; Token::Operator(Operator::Plus) }
-            '-' => { self.current += 1; Token::Operator(Operator::Minus) }
+            // -> only shows up in function types, (int) -> bool
+            '-' => {
+                self.current += 1;
+                if self.text.as_bytes().get(self.current) == Some(&b'>') {
+                    self.current += 1;
+                    return Token::Arrow;
+                }
+                Token::Operator(Operator::Minus)
+            }
             '*' => { self.current += 1; Token::Operator(Operator::Multiply) }
             '/' => { self.current += 1; Token::Operator(Operator::Divide) }
-            '<' => { self.current += 1; Token::Operator(Operator::LessThan) }
-            '>' => { self.current += 1; Token::Operator(Operator::GreaterThan) }
+            '%' => { self.current += 1; Token::Operator(Operator::Modulo) }
+            '<' => {
+                self.current += 1;
+                match self.text.as_bytes().get(self.current) {
+                    Some(b'<') => { self.current += 1; Token::Operator(Operator::ShiftLeft) }
+                    Some(b'=') => { self.current += 1; Token::Operator(Operator::LessEqual) }
+                    _ => Token::Operator(Operator::LessThan),
+                }
+            }
+            '>' => {
+                self.current += 1;
+                match self.text.as_bytes().get(self.current) {
+                    Some(b'>') => { self.current += 1; Token::Operator(Operator::ShiftRight) }
+                    Some(b'=') => { self.current += 1; Token::Operator(Operator::GreaterEqual) }
+                    _ => Token::Operator(Operator::GreaterThan),
+                }
+            }
             '|' => { self.current += 1; Token::Operator(Operator::BitwiseOr) }
 
             '=' => {
@@ -115,24 +333,12 @@ impl Tokenizer {
             '_' => { self.current += 1; Token::Identifier("_".to_string()) }
             
             // Tokenizing Digits
-            _ if ch.is_ascii_digit() => {
-                let start = self.current;
-                self.current += 1;
-                // This while look will allows us to tokenize digits of any length
-                while self.current < self.text.len() {
-                    let next_ch = self.text.as_bytes()[self.current] as char;
-                    if !next_ch.is_ascii_digit() {
-                        break;
-                    }
-                    self.current += 1;
-                }
-                let num_str = &self.text[start..self.current];
-                let value = num_str.parse::<i64>().unwrap();
-                Token::Number(value)
-            }
+            _ if ch.is_ascii_digit() => self.scan_number(),
+
+            '"' => self.scan_string(),
             
             // This is going to tokenize keywords and identifiers
-            _ if ch.is_alphabetic() => {
+            _ if ch.is_ascii_alphabetic() => {
                 let start = self.current;
                 self.current += 1;
                 while self.current < self.text.len() {
@@ -141,7 +347,7 @@ impl Tokenizer {
                     // And contain letters or number
                     // Like identifer1 or abc123
                     // But not 123abc or 1
-                    if !next_ch.is_alphanumeric() {
+                    if !next_ch.is_ascii_alphanumeric() {
                         break;
                     }
                     self.current += 1;
@@ -150,24 +356,51 @@ impl Tokenizer {
                 match fragment {
                     "if" => Token::If,
                     "else" => Token::Else,
+                    "elif" => Token::Elif,
                     "ifonly" => Token::IfOnly,
                     "while" => Token::While,
+                    "for" => Token::For,
+                    "to" => Token::To,
+                    "break" => Token::Break,
+                    "continue" => Token::Continue,
+                    "match" => Token::Match,
+                    "throw" => Token::Throw,
+                    "try" => Token::Try,
+                    "catch" => Token::Catch,
+                    "var" => Token::Var,
                     "return" => Token::Return,
                     "print" => Token::Print,
+                    "len" => Token::Len,
                     "this" => Token::This,
                     "class" => Token::Class,
                     "fields" => Token::Fields,
                     "method" => Token::Method,
+                    "constructor" => Token::Constructor,
                     "with" => Token::With,
+                    "extends" => Token::Extends,
+                    "interface" => Token::Interface,
+                    "implements" => Token::Implements,
+                    "function" => Token::Function,
+                    "import" => Token::Import,
+                    "enum" => Token::Enum,
                     "locals" => Token::Locals,
                     "main" => Token::Main,
                     "returning" => Token::Returning,
                     "null" => Token::Null,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    "and" => Token::Operator(Operator::And),
+                    "or" => Token::Operator(Operator::Or),
                     _ => Token::Identifier(fragment.to_string()),
                 }
             }
             
-            _ => panic!("Unsupported character: {}", ch),
+            _ => {
+                // step over the whole character so we dont land in the middle of a multibyte one
+                let ch = self.text[self.current..].chars().next().unwrap();
+                self.current += ch.len_utf8();
+                Token::Error(format!("unsupported character '{}'", ch))
+            }
         }
     }
 }
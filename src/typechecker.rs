@@ -1,261 +1,1734 @@
 use std::collections::HashMap;
-use crate::ast::{Program, Class, Type};
-use crate::expression::Expression;
-use crate::statement::Statement;
-use crate::token::Operator;
+use std::collections::HashSet;
+use crate::diagnostics::Severity;
+use crate::ast::{Program, Class, Declaration, Enum, Interface, Method, MethodSignature, Type};
+use crate::captures::captures;
+use crate::expression::{Expression, ExpressionKind, Lambda};
+use crate::span::Span;
+use crate::statement::{Catch, MatchArm, Statement, StatementKind};
+use crate::token::{Operator, UnaryOperator};
 
 pub struct TypeChecker {
     // class name -> class def for field/method lookup for checking type compatibility
     classes: HashMap<String, Class>,
+    // interface name -> the methods an implementing class has to provide
+    interfaces: HashMap<String, Interface>,
+    // enum name -> its variants, enums share the type namespace with classes and interfaces
+    enums: HashMap<String, Enum>,
+    // top level function name -> its signature
+    functions: HashMap<String, MethodSignature>,
+    // how many loops the statement being checked is inside of, break and continue need at least one
+    loop_depth: usize,
+    // the type parameters of the generic class being checked, they can be used like any other type in it
+    type_params: Vec<String>,
+    // the generic class being checked, and every generic class it uses with its type arguments,
+    // ex: Foo<Foo<T>> inside Foo. check_instantiations looks for ones that would never stop growing
+    generic_class: Option<String>,
+    instantiations: Vec<(String, String, Vec<Type>, Span)>,
+    // variables known to hold an object at the statement being checked. flow sensitive:
+    // a null test or assigning an object adds a name, assigning something that might be null takes it away.
+    // inside a constructor this.f is in here once the constructor has set field f
+    non_null: HashSet<String>,
+    // non-null fields the constructor being checked has to set before this can be used or returned
+    constructor_fields: Vec<(String, Span)>,
+
+    // every error found so far, we keep checking after the first one
+    errors: Vec<TypeError>,
+    // these dont fail the compile, main prints them either way
+    warnings: Vec<TypeError>,
+}
+
+// the codes are stable so tools and docs can refer to them, only ever add new ones at the end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    UnknownClass,
+    DuplicateClass,
+    UndefinedVariable,
+    ThisOutsideMethod,
+    EqualityMismatch,
+    NonIntOperand,
+    NoSuchField,
+    NoSuchMethod,
+    NotAnObject,
+    WrongArgCount,
+    ArgTypeMismatch,
+    FieldTypeMismatch,
+    PrintNonInt,
+    ReturnTypeMismatch,
+    AssignmentMismatch,
+    NonBoolCondition,
+    InheritanceCycle,
+    FieldRedeclared,
+    IncompatibleOverride,
+    MissingInterfaceMethod,
+    NotAnInterface,
+    NotAClass,
+    NotAnArray,
+    NonIntIndex,
+    ArrayElementMismatch,
+    NonBoolOperand,
+    UnknownFunction,
+    DuplicateFunction,
+    OutsideLoop,
+    VariableRedeclared,
+    WrongTypeArgCount,
+    NullableDereference,
+    NotNullable,
+    NullInNonNullable,
+    UninitializedVariable,
+    UninitializedField,
+    // reported by the import loader before type checking starts
+    ImportNotFound,
+    ImportCycle,
+    NotAnEnum,
+    UnknownVariant,
+    NonExhaustiveMatch,
+    DuplicateVariant,
+    NotThrowable,
+    CapturedAssignment,
+    ExpandingGeneric,
+    // warnings are numbered separately
+    UnreachableCode,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnknownClass => "E0001",
+            ErrorCode::DuplicateClass => "E0002",
+            ErrorCode::UndefinedVariable => "E0003",
+            ErrorCode::ThisOutsideMethod => "E0004",
+            ErrorCode::EqualityMismatch => "E0005",
+            ErrorCode::NonIntOperand => "E0006",
+            ErrorCode::NoSuchField => "E0007",
+            ErrorCode::NoSuchMethod => "E0008",
+            ErrorCode::NotAnObject => "E0009",
+            ErrorCode::WrongArgCount => "E0010",
+            ErrorCode::ArgTypeMismatch => "E0011",
+            ErrorCode::FieldTypeMismatch => "E0012",
+            ErrorCode::PrintNonInt => "E0013",
+            ErrorCode::ReturnTypeMismatch => "E0014",
+            ErrorCode::AssignmentMismatch => "E0015",
+            ErrorCode::NonBoolCondition => "E0016",
+            ErrorCode::InheritanceCycle => "E0017",
+            ErrorCode::FieldRedeclared => "E0018",
+            ErrorCode::IncompatibleOverride => "E0019",
+            ErrorCode::MissingInterfaceMethod => "E0020",
+            ErrorCode::NotAnInterface => "E0021",
+            ErrorCode::NotAClass => "E0022",
+            ErrorCode::NotAnArray => "E0023",
+            ErrorCode::NonIntIndex => "E0024",
+            ErrorCode::ArrayElementMismatch => "E0025",
+            ErrorCode::NonBoolOperand => "E0026",
+            ErrorCode::UnknownFunction => "E0027",
+            ErrorCode::DuplicateFunction => "E0028",
+            ErrorCode::OutsideLoop => "E0029",
+            ErrorCode::VariableRedeclared => "E0030",
+            ErrorCode::WrongTypeArgCount => "E0031",
+            ErrorCode::NullableDereference => "E0032",
+            ErrorCode::NotNullable => "E0033",
+            ErrorCode::NullInNonNullable => "E0034",
+            ErrorCode::UninitializedVariable => "E0035",
+            ErrorCode::UninitializedField => "E0036",
+            ErrorCode::ImportNotFound => "E0037",
+            ErrorCode::ImportCycle => "E0038",
+            ErrorCode::NotAnEnum => "E0039",
+            ErrorCode::UnknownVariant => "E0040",
+            ErrorCode::NonExhaustiveMatch => "E0041",
+            ErrorCode::DuplicateVariant => "E0042",
+            ErrorCode::NotThrowable => "E0043",
+            ErrorCode::CapturedAssignment => "E0044",
+            ErrorCode::ExpandingGeneric => "E0045",
+            ErrorCode::UnreachableCode => "W0001",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ErrorCode::UnreachableCode => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Span,
+    // filled in for mismatches, ex: expected int, found ListNode
+    pub expected: Option<Type>,
+    pub found: Option<Type>,
+    // other places worth pointing at, ex: where the field being written was declared
+    pub notes: Vec<(String, Span)>,
 }
 
+// what a field read or method call is being done on, enough to look members up and to point at it
+struct ObjectType {
+    // "class" or "interface", for messages
+    kind: &'static str,
+    name: String,
+    span: Span,
+    // T -> int when the object is a Stack<int>, member types get these filled in
+    type_args: HashMap<String, Type>,
+}
+
+/*
+eval_type returns None when the expression has an error that was already reported.
+Callers skip their own checks on None, so one mistake doesnt turn into a pile of follow on errors
+*/
 impl TypeChecker {
     pub fn new(program: &Program) -> Self {
         let mut classes = HashMap::new();
         for class  in &program.classes {
             classes.insert(class.name.clone(), class.clone());
         }
-        TypeChecker { classes }
+        let mut interfaces = HashMap::new();
+        for interface in &program.interfaces {
+            interfaces.insert(interface.name.clone(), interface.clone());
+        }
+        let mut enums = HashMap::new();
+        for declared in &program.enums {
+            enums.insert(declared.name.clone(), declared.clone());
+        }
+        let mut functions = HashMap::new();
+        for function in &program.functions {
+            functions.insert(function.name.clone(), function.signature());
+        }
+        TypeChecker { classes, interfaces, enums, functions, loop_depth: 0, type_params: vec![], generic_class: None, instantiations: vec![], non_null: HashSet::new(), constructor_fields: vec![], errors: vec![], warnings: vec![] }
     }
 
-    fn validate_type(&self, typ: &Type) {
-        if let Type::ClassType(name) = typ {
-            if !self.classes.contains_key(name) {
-                panic!("Unknown class {}", name);
+    fn error(&mut self, code: ErrorCode, message: String, span: Span) {
+        self.errors.push(TypeError { code, message, span, expected: None, found: None, notes: vec![] });
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<TypeError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // attaches a note to the error that was just reported
+    fn note(&mut self, message: String, span: Span) {
+        if let Some(error) = self.errors.last_mut() {
+            error.notes.push((message, span));
+        }
+    }
+
+    fn mismatch(&mut self, code: ErrorCode, message: String, span: Span, expected: &Type, found: &Type) {
+        self.errors.push(TypeError {
+            code,
+            message,
+            span,
+            expected: Some(expected.clone()),
+            found: Some(found.clone()),
+            notes: vec![],
+        });
+    }
+
+    // false if the type was reported as unknown or has the wrong number of type arguments
+    fn validate_type(&mut self, typ: &Type, span: Span) -> bool {
+        match typ {
+            Type::ClassType(name) if self.type_params.contains(name) || self.interfaces.contains_key(name)
+                || self.enums.contains_key(name) => true,
+            Type::ClassType(name) => self.validate_type_args(name, &[], span),
+            Type::Generic(name, args) => self.validate_type_args(name, args, span),
+            Type::Array(element) => self.validate_type(element, span),
+            Type::Function(args, ret) => {
+                let mut valid = self.validate_type(ret, span);
+                for arg in args {
+                    valid &= self.validate_type(arg, span);
+                }
+                valid
             }
+            // only objects, strs, and arrays can be null, ints and bools cant.
+            // a type parameter can be, T? is plain int when T is int (see Type::substitute)
+            Type::Nullable(inner) => match inner.as_ref() {
+                Type::ClassType(_) | Type::Generic(..) | Type::Str | Type::Array(_) => self.validate_type(inner, span),
+                other => {
+                    self.error(ErrorCode::NotNullable,
+                        format!("{} cannot be nullable, only class types, strs, and arrays can", other), span);
+                    false
+                }
+            },
+            _ => true,
         }
     }
 
-    pub fn check_program(&self, program: &Program) {
-        // check all type exist
+    // a generic class has to be given exactly as many type arguments as it has parameters, other classes none
+    fn validate_type_args(&mut self, name: &str, args: &[Type], span: Span) -> bool {
+        let Some(class) = self.classes.get(name) else {
+            if self.interfaces.contains_key(name) {
+                self.error(ErrorCode::WrongTypeArgCount, format!("interface `{}` is not generic", name), span);
+            } else if self.enums.contains_key(name) {
+                self.error(ErrorCode::WrongTypeArgCount, format!("enum `{}` is not generic", name), span);
+            } else {
+                self.error(ErrorCode::UnknownClass, format!("unknown class `{}`", name), span);
+            }
+            return false;
+        };
+        let (params, class_span) = (class.type_params.len(), class.span);
+        if params != args.len() {
+            let message = if params == 0 {
+                format!("class `{}` is not generic", name)
+            } else {
+                format!("class `{}` takes {} type argument(s) but {} were given", name, params, args.len())
+            };
+            self.error(ErrorCode::WrongTypeArgCount, message, span);
+            self.note(format!("`{}` declared here", name), class_span);
+            return false;
+        }
+        // keep going after a bad one so every bad argument gets reported
+        let mut valid = true;
+        for arg in args {
+            valid &= self.validate_type(arg, span);
+        }
+        if let Some(inside) = &self.generic_class
+            && !args.is_empty() && args.iter().any(|arg| self.type_params.iter().any(|param| arg.mentions(param))) {
+            let used = (inside.clone(), name.to_string(), args.to_vec(), span);
+            if !self.instantiations.contains(&used) {
+                self.instantiations.push(used);
+            }
+        }
+        valid
+    }
+
+    /*
+    monomorphizing makes one copy per instantiation, so a generic class can't use itself
+    with a type argument that wraps its own parameter:
+
+        class Foo<T> [ ... x:Foo<Foo<T>>? ... ]
+
+    Foo<int> needs Foo<Foo<int>>, which needs Foo<Foo<Foo<int>>>, and so on forever.
+    Each type parameter is a node, Foo<Foo<T>> inside Foo is an edge from Foo's T to Foo's T
+    that grows the type, and a cycle through a growing edge (even across several classes) never stops
+    */
+    fn check_instantiations(&mut self) {
+        // (class, index of its type parameter) -> every parameter it gets passed on to
+        let mut edges: HashMap<(String, usize), Vec<(String, usize)>> = HashMap::new();
+        let mut growing = vec![];
+        for (inside, used, args, span) in &self.instantiations {
+            let params = &self.classes[inside].type_params;
+            for (j, arg) in args.iter().enumerate() {
+                for (i, param) in params.iter().enumerate() {
+                    if !arg.mentions(param) {
+                        continue;
+                    }
+                    let grows = *arg != Type::ClassType(param.clone());
+                    let (from, to) = ((inside.clone(), i), (used.clone(), j));
+                    edges.entry(from.clone()).or_default().push(to.clone());
+                    if grows {
+                        growing.push((from, to, Type::Generic(used.clone(), args.clone()), inside.clone(), *span));
+                    }
+                }
+            }
+        }
+
+        let mut reported = vec![];
+        for (from, to, typ, inside, span) in growing {
+            // can the grown type find its way back to where it started
+            let mut seen = HashSet::from([to.clone()]);
+            let mut stack = vec![to];
+            while let Some(node) = stack.pop() {
+                for next in edges.get(&node).into_iter().flatten() {
+                    if seen.insert(next.clone()) {
+                        stack.push(next.clone());
+                    }
+                }
+            }
+            if seen.contains(&from) && !reported.contains(&span) {
+                reported.push(span);
+                let class_span = self.classes[&inside].span;
+                self.error(ErrorCode::ExpandingGeneric,
+                    format!("`{}` inside `{}` leads back to `{}` with a bigger type argument every time, so it has no end of instantiations",
+                        typ, inside, inside), span);
+                self.note(format!("`{}` declared here", inside), class_span);
+            }
+        }
+    }
+
+    // T -> int for the class Stack<T> used as a Stack<int>
+    fn type_arg_map(&self, class_name: &str, args: &[Type]) -> HashMap<String, Type> {
+        match self.classes.get(class_name) {
+            Some(class) => class.type_params.iter().cloned().zip(args.iter().cloned()).collect(),
+            None => HashMap::new(),
+        }
+    }
+
+    // the class followed by its superclass, its superclass's superclass, and so on
+    // stops early at an unknown superclass or a cycle, those are reported by check_hierarchy
+    fn ancestors(&self, class_name: &str) -> Vec<&Class> {
+        let mut chain: Vec<&Class> = vec![];
+        let mut seen = HashSet::new();
+        let mut current = self.classes.get(class_name);
+        while let Some(class) = current {
+            if !seen.insert(class.name.as_str()) {
+                break;
+            }
+            chain.push(class);
+            current = class.superclass.as_ref().and_then(|(name, _)| self.classes.get(name));
+        }
+        chain
+    }
+
+    // fields and methods are looked up starting at the class itself and then up through its superclasses
+    fn find_field(&self, class_name: &str, field_name: &str) -> Option<Declaration> {
+        self.ancestors(class_name).into_iter()
+            .find_map(|class| class.fields.iter().find(|f| f.name == field_name))
+            .cloned()
+    }
+
+    // works on interface types too, where only the declared signatures are known
+    fn find_method(&self, type_name: &str, method_name: &str) -> Option<MethodSignature> {
+        if let Some(interface) = self.interfaces.get(type_name) {
+            return interface.methods.iter().find(|m| m.name == method_name).cloned();
+        }
+        self.ancestors(type_name).into_iter()
+            .find_map(|class| class.methods.iter().find(|m| m.name == method_name))
+            .map(|m| m.signature())
+    }
+
+    // a B can be used wherever an A is expected if B is A or extends it somewhere up the chain,
+    // and wherever an interface is expected if B or one of its superclasses implements it
+    // a Stack<int> is only ever a Stack<int>, but it is still whatever Stack extends or implements.
+    // an A can go anywhere an A? can, but not the other way around
+    fn is_subtype(&self, sub: &Type, sup: &Type) -> bool {
+        match (sub, sup) {
+            (Type::Nullable(sub), Type::Nullable(sup)) => self.is_subtype(sub, sup),
+            (sub, Type::Nullable(sup)) => self.is_subtype(sub, sup),
+            (Type::ClassType(sub) | Type::Generic(sub, _), Type::ClassType(sup)) if !self.type_params.contains(sub) => {
+                sub == sup || self.ancestors(sub).iter().any(|class| {
+                    class.name == *sup || class.interfaces.iter().any(|(name, _)| name == sup)
+                })
+            }
+            // a function that takes anything the expected one would be given, and returns something that fits
+            (Type::Function(sub_args, sub_ret), Type::Function(sup_args, sup_ret)) => {
+                sub_args.len() == sup_args.len()
+                    && sub_args.iter().zip(sup_args).all(|(sub_arg, sup_arg)| self.is_subtype(sup_arg, sub_arg))
+                    && self.is_subtype(sub_ret, sup_ret)
+            }
+            _ => sub == sup,
+        }
+    }
+
+    // the method's argument types match exactly and its return type fits in the expected one
+    fn signature_matches(&self, method: &MethodSignature, expected: &MethodSignature) -> bool {
+        method.args.len() == expected.args.len()
+            && method.args.iter().zip(&expected.args).all(|(a, b)| a.typ == b.typ)
+            && self.is_subtype(&method.return_type, &expected.return_type)
+    }
+
+    /*
+    extends has to point at a real class, cant loop back around, and a subclass cant
+    redeclare a field it already inherits since both would need the same slot.
+    overriding a method has to keep the same argument types, and can narrow the return type
+    to a subclass, so calling through the superclass's signature stays safe
+    */
+    fn check_hierarchy(&mut self, program: &Program) {
         for class in &program.classes {
-            for (_, typ) in &class.fields {
-                self.validate_type(typ);
+            self.check_implements(class);
+
+            let Some((superclass, super_span)) = &class.superclass else { continue };
+            if self.interfaces.contains_key(superclass) {
+                self.error(ErrorCode::NotAClass,
+                    format!("cannot extend interface `{}`, use `implements` instead", superclass), *super_span);
+                continue;
+            }
+            let Some(generic) = self.classes.get(superclass).map(|c| !c.type_params.is_empty()) else {
+                self.error(ErrorCode::UnknownClass, format!("unknown class `{}`", superclass), *super_span);
+                continue;
+            };
+            if generic {
+                self.error(ErrorCode::WrongTypeArgCount,
+                    format!("cannot extend generic class `{}`", superclass), *super_span);
+                continue;
+            }
+            if self.ancestors(superclass).iter().any(|c| c.name == class.name) {
+                self.error(ErrorCode::InheritanceCycle,
+                    format!("class `{}` inherits from itself", class.name), *super_span);
+                continue;
+            }
+
+            for field in &class.fields {
+                if let Some(inherited) = self.find_field(superclass, &field.name) {
+                    self.error(ErrorCode::FieldRedeclared,
+                        format!("field `{}` is already inherited from `{}`", field.name, superclass), field.span);
+                    self.note(format!("`{}` first declared here", field.name), inherited.span);
+                }
             }
 
-            // check the return types, arguments, and locals types for each method
             for method in &class.methods {
-                self.validate_type(&method.return_type);
-                for (_, typ) in &method.args {
-                    self.validate_type(typ);
+                let Some(overridden) = self.find_method(superclass, &method.name) else { continue };
+                let same_args = method.args.len() == overridden.args.len()
+                    && method.args.iter().zip(&overridden.args).all(|(a, b)| a.typ == b.typ);
+                if !same_args {
+                    self.error(ErrorCode::IncompatibleOverride,
+                        format!("`{}.{}` overrides a method with different argument types", class.name, method.name),
+                        method.span);
+                    self.note(format!("overridden method `{}` declared here", method.name), overridden.span);
+                } else if !self.is_subtype(&method.return_type, &overridden.return_type) {
+                    self.mismatch(ErrorCode::IncompatibleOverride,
+                        format!("`{}.{}` overrides a method with an incompatible return type", class.name, method.name),
+                        method.span, &overridden.return_type, &method.return_type);
+                    self.note(format!("overridden method `{}` declared here", method.name), overridden.span);
+                }
+            }
+        }
+    }
+
+    // a class has to provide every method of every interface it implements, inherited ones count
+    fn check_implements(&mut self, class: &Class) {
+        for (name, span) in &class.interfaces {
+            let Some(interface) = self.interfaces.get(name).cloned() else {
+                if self.classes.contains_key(name) {
+                    self.error(ErrorCode::NotAnInterface,
+                        format!("`{}` is a class, not an interface", name), *span);
+                } else {
+                    self.error(ErrorCode::UnknownClass, format!("unknown interface `{}`", name), *span);
                 }
-                
-                for (_, typ) in &method.locals {
-                    self.validate_type(typ);
+                continue;
+            };
+
+            for required in &interface.methods {
+                match self.find_method(&class.name, &required.name) {
+                    None => {
+                        self.error(ErrorCode::MissingInterfaceMethod,
+                            format!("class `{}` is missing method `{}` from interface `{}`", class.name, required.name, name),
+                            *span);
+                        self.note(format!("`{}.{}` declared here", name, required.name), required.span);
+                    }
+                    Some(method) if !self.signature_matches(&method, required) => {
+                        self.error(ErrorCode::IncompatibleOverride,
+                            format!("`{}.{}` does not match the signature in interface `{}`", class.name, required.name, name),
+                            method.span);
+                        self.note(format!("`{}.{}` declared here", name, required.name), required.span);
+                    }
+                    Some(_) => {}
                 }
             }
         }
+    }
+
+    pub fn check_program(&mut self, program: &Program) -> Result<(), Vec<TypeError>> {
+        // the class map silently keeps the last one, so catch redefinitions here
+        // classes, interfaces, and enums share one namespace since they are all used as types
+        let declared: Vec<(&String, Span)> = program.classes.iter().map(|c| (&c.name, c.span))
+            .chain(program.interfaces.iter().map(|i| (&i.name, i.span)))
+            .chain(program.enums.iter().map(|e| (&e.name, e.span)))
+            .collect();
+        for (i, (name, span)) in declared.iter().enumerate() {
+            if let Some((_, first)) = declared[..i].iter().find(|(other, _)| other == name) {
+                self.error(ErrorCode::DuplicateClass, format!("`{}` is declared twice", name), *span);
+                self.note(format!("`{}` first declared here", name), *first);
+            }
+        }
+
+        // functions get their own namespace, f(x) can only ever be a function call
+        for (i, function) in program.functions.iter().enumerate() {
+            if let Some(first) = program.functions[..i].iter().find(|other| other.name == function.name) {
+                self.error(ErrorCode::DuplicateFunction,
+                    format!("function `{}` is declared twice", function.name), function.span);
+                self.note(format!("`{}` first declared here", function.name), first.span);
+            }
+        }
+
+        for interface in &program.interfaces {
+            for method in &interface.methods {
+                self.validate_type(&method.return_type, method.span);
+                for arg in &method.args {
+                    self.validate_type(&arg.typ, arg.span);
+                }
+            }
+        }
+
+        for declared in &program.enums {
+            for (i, variant) in declared.variants.iter().enumerate() {
+                if let Some(first) = declared.variants[..i].iter().find(|other| other.name == variant.name) {
+                    self.error(ErrorCode::DuplicateVariant,
+                        format!("enum `{}` declares variant `{}` twice", declared.name, variant.name), variant.span);
+                    self.note(format!("`{}` first declared here", variant.name), first.span);
+                }
+                for field in &variant.fields {
+                    self.validate_type(&field.typ, field.span);
+                }
+            }
+        }
+
+        self.check_hierarchy(program);
+
+        // check all type exist
+        for class in &program.classes {
+            self.type_params = class.type_params.clone();
+            self.generic_class = (!class.type_params.is_empty()).then(|| class.name.clone());
+            for field in &class.fields {
+                self.validate_type(&field.typ, field.span);
+            }
+
+            for method in class.methods.iter().chain(&class.constructor) {
+                self.validate_method_types(method);
+            }
+        }
+        self.type_params.clear();
+        self.generic_class = None;
+        for function in &program.functions {
+            self.validate_method_types(function);
+        }
 
         // check main locals types exist
-        for (_, typ) in &program.main_locals {
-            self.validate_type(typ);
+        for local in &program.main_locals {
+            self.validate_type(&local.typ, local.span);
         }
 
         for class in &program.classes {
+            // inside class Stack<T>, this is a Stack<T> and T is a type we know nothing else about
+            self.type_params = class.type_params.clone();
+            self.generic_class = (!class.type_params.is_empty()).then(|| class.name.clone());
+            let this = if class.type_params.is_empty() {
+                Type::ClassType(class.name.clone())
+            } else {
+                Type::Generic(class.name.clone(), class.type_params.iter().cloned().map(Type::ClassType).collect())
+            };
             for method in &class.methods {
-                let mut env = HashMap::new();
-                env.insert("this".to_string(), Type::ClassType(class.name.clone()));
-                for (name, typ) in &method.args {
-                    env.insert(name.clone(), typ.clone());
-                }
+                self.check_body(method, Some(this.clone()));
+            }
 
-                for (name, typ) in &method.locals {
-                    env.insert(name.clone(), typ.clone());
+            // @A hands back an object with every field zeroed, so a field that can't be null
+            // (inherited ones too) has to be set by the constructor
+            let required: Vec<(String, Span)> = self.ancestors(&class.name).iter()
+                .flat_map(|ancestor| &ancestor.fields)
+                .filter(|field| holds_object(&field.typ))
+                .map(|field| (field.name.clone(), field.span))
+                .collect();
+            match &class.constructor {
+                // the constructor body is checked just like a method, this is the new object
+                Some(constructor) => {
+                    self.constructor_fields = required;
+                    self.check_body(constructor, Some(this.clone()));
+                    self.constructor_fields.clear();
                 }
-
-                for statement in &method.body {
-                    self.check_statement(statement, &env, &method.return_type);
+                None => {
+                    for (field, span) in required {
+                        self.error(ErrorCode::UninitializedField,
+                            format!("class `{}` has no constructor to set non-null field `{}`", class.name, field),
+                            class.span);
+                        self.note(format!("`{}` declared here, make it nullable or set it in a constructor", field), span);
+                    }
                 }
             }
         }
+        self.type_params.clear();
+        self.generic_class = None;
+        self.check_instantiations();
+        for function in &program.functions {
+            self.check_body(function, None);
+        }
 
+        // main's locals start out zeroed just like a method's
+        self.non_null.clear();
         let mut env = HashMap::new();
-        for (name, typ) in &program.main_locals {
-            env.insert(name.clone(), typ.clone());
+        for local in &program.main_locals {
+            env.insert(local.name.clone(), local.typ.clone());
         }
 
-        for statement in &program.main_body {
-            self.check_statement(statement, &env, &Type::Int);
+        self.check_block(&program.main_body, &env, &Type::Int);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
         }
     }
 
-    fn eval_type(&self, expr: &Expression, env: &HashMap<String, Type>) -> Type {
-        match expr {
-            Expression::Constant(_) => Type::Int,
+    // check the return types, arguments, and locals types for a method or function
+    fn validate_method_types(&mut self, method: &Method) {
+        self.validate_type(&method.return_type, method.span);
+        for arg in &method.args {
+            self.validate_type(&arg.typ, arg.span);
+        }
 
-            Expression::Variable(name) => {
-                env.get(name).unwrap_or_else(|| panic!("Undefined variable {}", name)).clone()
-            }
+        for local in &method.locals {
+            self.validate_type(&local.typ, local.span);
+        }
+    }
+
+    // functions have no this, so using it in one is the same error as using it in main
+    fn check_body(&mut self, method: &Method, this: Option<Type>) {
+        let mut env = HashMap::new();
+        if let Some(this) = this {
+            env.insert("this".to_string(), this);
+        }
+        // arguments were checked by the caller, locals start out zeroed and have to be assigned first
+        self.non_null.clear();
+        for arg in &method.args {
+            env.insert(arg.name.clone(), arg.typ.clone());
+            self.non_null.insert(arg.name.clone());
+        }
+
+        for local in &method.locals {
+            env.insert(local.name.clone(), local.typ.clone());
+        }
+
+        self.check_block(&method.body, &env, &method.return_type);
+        if !method.body.iter().any(always_exits) {
+            self.check_fields_set(method.span, "finishes");
+        }
+    }
+
+    // a constructor cant hand back (or hand out) its object until every non-null field is set
+    fn check_fields_set(&mut self, span: Span, what: &str) -> bool {
+        let unset: Vec<(String, Span)> = self.constructor_fields.iter()
+            .filter(|(field, _)| !self.non_null.contains(&this_field(field)))
+            .cloned()
+            .collect();
+        for (field, declared) in &unset {
+            self.error(ErrorCode::UninitializedField,
+                format!("constructor {} before setting non-null field `{}`", what, field), span);
+            self.note(format!("`{}` declared here", field), *declared);
+        }
+        unset.is_empty()
+    }
+
+    // after x = e (or var x = e), x holds an object unless e might be null
+    fn bind(&mut self, name: &str, value_type: Option<&Type>) {
+        match value_type {
+            Some(Type::Nullable(_)) => self.non_null.remove(name),
+            _ => self.non_null.insert(name.to_string()),
+        };
+    }
+
+    fn eval_type(&mut self, expr: &Expression, env: &HashMap<String, Type>) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::Constant(_) => Some(Type::Int),
+
+            ExpressionKind::Bool(_) => Some(Type::Bool),
 
-            Expression::ThisExpr => {
-                env.get("this").expect("this used outside of method").clone()
+            ExpressionKind::StringLiteral(_) => Some(Type::Str),
+
+            ExpressionKind::Variable(name) => {
+                // a top level function used by name is a function value
+                if !env.contains_key(name)
+                    && let Some(function) = self.functions.get(name) {
+                    return Some(function_type(function));
+                }
+                let Some(typ) = env.get(name).cloned() else {
+                    self.error(ErrorCode::UndefinedVariable, format!("undefined variable `{}`", name), expr.span);
+                    return None;
+                };
+                match typ {
+                    // a null test already ruled null out
+                    Type::Nullable(inner) if self.non_null.contains(name) => Some(*inner),
+                    typ if holds_object(&typ) && !self.non_null.contains(name) => {
+                        self.error(ErrorCode::UninitializedVariable,
+                            format!("`{}` is used before it is assigned, so it could still be null", name), expr.span);
+                        // only say so once
+                        self.non_null.insert(name.clone());
+                        Some(typ)
+                    }
+                    typ => Some(typ),
+                }
             }
 
-            Expression::Null(class_name) => {
-                let typ = Type::ClassType(class_name.clone());
-                self.validate_type(&typ);
+            ExpressionKind::ThisExpr => {
+                let typ = env.get("this").cloned();
+                if typ.is_none() {
+                    self.error(ErrorCode::ThisOutsideMethod, "`this` used outside of a method".to_string(), expr.span);
+                }
+                self.check_fields_set(expr.span, "uses `this`");
                 typ
             }
 
-            Expression::Binop { op, lhs, rhs } => {
+            ExpressionKind::Null(typ) => {
+                if !matches!(typ, Type::Nullable(_)) {
+                    self.error(ErrorCode::NullInNonNullable,
+                        format!("`null` needs a nullable type, write `null:{}?`", typ), expr.span);
+                    return None;
+                }
+                self.validate_type(typ, expr.span).then(|| typ.clone())
+            }
+
+            ExpressionKind::Binop { op, lhs, rhs } => {
                 let ltyp = self.eval_type(lhs, env);
-                let rtyp = self.eval_type(rhs, env);
+                let rtyp = if matches!(op, Operator::And | Operator::Or) {
+                    // the right side only runs when the left side didnt decide, so it can count on what that proved
+                    // p != null:A? and &p.x > 0 is fine
+                    let (when_true, when_false) = null_tests(lhs);
+                    let before = self.non_null.clone();
+                    self.non_null.extend(if *op == Operator::And { when_true } else { when_false });
+                    let rtyp = self.eval_type(rhs, env);
+                    self.non_null = before;
+                    rtyp
+                } else {
+                    self.eval_type(rhs, env)
+                };
                 match op {
                     Operator::Equals | Operator::NotEquals => {
-                        match (&ltyp, &rtyp) {
-                            (Type::Int, Type::Int) => Type::Int,
-                            (Type::ClassType(a), Type::ClassType(b)) if a == b => Type::Int,
-                            _ => panic!("Equality operands must have matching types"),
+                        // objects can be compared when one could be the other, ex: an A and a B that extends A
+                        if let (Some(ltyp), Some(rtyp)) = (&ltyp, &rtyp)
+                            && !self.is_subtype(ltyp, rtyp) && !self.is_subtype(rtyp, ltyp) {
+                            self.mismatch(ErrorCode::EqualityMismatch,
+                                format!("operands of `{}` must have matching types", op),
+                                rhs.span, ltyp, rtyp);
+                        }
+                        Some(Type::Bool)
+                    }
+                    Operator::And | Operator::Or => {
+                        for (operand, typ) in [(lhs, &ltyp), (rhs, &rtyp)] {
+                            self.expect_operand(operand, typ, &Type::Bool,
+                                format!("operands of `{}` must be bools", op));
                         }
+                        Some(Type::Bool)
                     }
                     _ => {
-                        if ltyp != Type::Int || rtyp != Type::Int {
-                            panic!("Binary op requires ints");
+                        for (operand, typ) in [(lhs, &ltyp), (rhs, &rtyp)] {
+                            self.expect_operand(operand, typ, &Type::Int,
+                                format!("operands of `{}` must be ints", op));
+                        }
+                        // comparisons answer yes or no, everything else is arithmetic
+                        match op {
+                            Operator::LessThan | Operator::GreaterThan
+                            | Operator::LessEqual | Operator::GreaterEqual => Some(Type::Bool),
+                            _ => Some(Type::Int),
                         }
-                        Type::Int
                     }
                 }
             }
 
-            Expression::ClassRef(name) => {
-                if !self.classes.contains_key(name) {
-                    panic!("Unknown class of {}", name);
+            ExpressionKind::Unary { op, operand } => {
+                let typ = self.eval_type(operand, env);
+                if *op == UnaryOperator::Not {
+                    self.expect_operand(operand, &typ, &Type::Bool, format!("operand of `{}` must be a bool", op));
+                    Some(Type::Bool)
+                } else {
+                    self.expect_operand(operand, &typ, &Type::Int, format!("operand of `{}` must be an int", op));
+                    Some(Type::Int)
                 }
-                Type::ClassType(name.clone())
             }
 
-            Expression::FieldRead { base, field_name } => {
-                let base_type = self.eval_type(base, env);
-                match &base_type {
-                    Type::ClassType(class_name) => {
-                        let class = self.classes.get(class_name)
-                            .unwrap_or_else(|| panic!("Unknown class {}", class_name));
-                        for (fname, ftyp) in &class.fields {
-                            if fname == field_name {
-                                return ftyp.clone();
-                            }
-                        }
-                        panic!("Class {} has no field {}", class_name, field_name);
+            ExpressionKind::ClassRef { class_name: name, type_args, args } => {
+                if let Some(interface) = self.interfaces.get(name) {
+                    let span = interface.span;
+                    self.error(ErrorCode::NotAClass, format!("cannot allocate interface `{}`", name), expr.span);
+                    self.note(format!("`{}` declared here", name), span);
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                }
+                if let Some(declared) = self.enums.get(name) {
+                    let (span, first) = (declared.span, declared.variants[0].name.clone());
+                    self.error(ErrorCode::NotAClass,
+                        format!("cannot allocate enum `{}` by itself, pick a variant like `@{}.{}`", name, name, first),
+                        expr.span);
+                    self.note(format!("`{}` declared here", name), span);
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                }
+                if !self.validate_type_args(name, type_args, expr.span) {
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                }
+                let class = self.classes[name].clone();
+                let typ = if type_args.is_empty() {
+                    Type::ClassType(name.clone())
+                } else {
+                    Type::Generic(name.clone(), type_args.clone())
+                };
+
+                // without a constructor @A is the only way to allocate one
+                match &class.constructor {
+                    Some(constructor) => {
+                        let callee = format!("the constructor of `{}`", typ);
+                        let signature = substitute_signature(constructor.signature(), &self.type_arg_map(name, type_args));
+                        self.check_args(args, &signature, &callee, expr.span, env);
+                    }
+                    None if !args.is_empty() => {
+                        self.error(ErrorCode::WrongArgCount,
+                            format!("class `{}` has no constructor but {} argument(s) were given", name, args.len()),
+                            expr.span);
+                        self.note(format!("`{}` declared here", name), class.span);
+                        for arg in args { self.eval_type(arg, env); }
+                    }
+                    None => {}
+                }
+                Some(typ)
+            }
+
+            // @Shape.Circle(5) checks its arguments against the variant's fields like a constructor call
+            ExpressionKind::Variant { enum_name, variant, args } => {
+                let Some(declared) = self.enums.get(enum_name).cloned() else {
+                    if self.classes.contains_key(enum_name) || self.interfaces.contains_key(enum_name) {
+                        self.error(ErrorCode::NotAnEnum, format!("`{}` is not an enum", enum_name), expr.span);
+                    } else {
+                        self.error(ErrorCode::UnknownClass, format!("unknown enum `{}`", enum_name), expr.span);
                     }
-                    Type::Int => panic!("Cant read field of int"),
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                };
+                let Some((_, found)) = declared.variant(variant) else {
+                    self.error(ErrorCode::UnknownVariant,
+                        format!("enum `{}` has no variant `{}`", enum_name, variant), expr.span);
+                    self.note(format!("`{}` declared here", enum_name), declared.span);
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                };
+                let signature = MethodSignature {
+                    name: variant.clone(),
+                    args: found.fields.clone(),
+                    return_type: Type::Int,
+                    span: found.span,
+                };
+                self.check_args(args, &signature, &format!("`{}.{}`", enum_name, variant), expr.span, env);
+                Some(Type::ClassType(enum_name.clone()))
+            }
+
+            ExpressionKind::ArrayAlloc { element, length } => {
+                self.check_index(length, env, "length");
+                if holds_object(element) {
+                    self.error(ErrorCode::NullInNonNullable,
+                        format!("the elements of a new array start out null, allocate it as `@{}?[...]`", element),
+                        expr.span);
+                    return None;
                 }
+                self.validate_type(element, expr.span).then(|| Type::Array(Box::new(element.clone())))
             }
 
-            Expression::MethodCall { base, method_name, args } => {
-                let base_type = self.eval_type(base, env);
-                match &base_type {
-                    Type::ClassType(class_name) => {
-                        let class = self.classes.get(class_name).unwrap();
-                        let method = class.methods.iter()
-                            .find(|m| m.name == *method_name)
-                            .unwrap_or_else(|| panic!("the claslass {} has no method {}", class_name, method_name));
+            ExpressionKind::Index { array, index } => {
+                let element = self.array_element(array, env, "index into", true);
+                self.check_index(index, env, "index");
+                element
+            }
 
-                        if args.len() != method.args.len() {
-                            panic!("Incorrect number of args for {}.{}", class_name, method_name);
-                        }
-                        for (arg_expr, (_, expected_type)) in args.iter().zip(method.args.iter()) {
-                            let actual = self.eval_type(arg_expr, env);
-                            if actual != *expected_type {
-                                panic!("Arg type mismatch in {}.{}", class_name, method_name);
-                            }
-                        }
+            ExpressionKind::Length(array) => {
+                self.array_element(array, env, "take the length of", true)?;
+                Some(Type::Int)
+            }
+
+            ExpressionKind::FieldRead { base, field_name } => {
+                let class = self.base_class(base, env, &format!("read field `{}`", field_name))?;
+                if matches!(base.kind, ExpressionKind::ThisExpr)
+                    && self.constructor_fields.iter().any(|(field, _)| field == field_name)
+                    && !self.non_null.contains(&this_field(field_name)) {
+                    self.error(ErrorCode::UninitializedField,
+                        format!("field `{}` is read before the constructor sets it", field_name), expr.span);
+                }
+                let field_type = self.find_field(&class.name, field_name).map(|f| f.typ.substitute(&class.type_args));
+                if field_type.is_none() {
+                    self.error(ErrorCode::NoSuchField,
+                        format!("{} `{}` has no field `{}`", class.kind, class.name, field_name), expr.span);
+                    self.note(format!("`{}` declared here", class.name), class.span);
+                }
+                field_type
+            }
 
-                        method.return_type.clone()
+            ExpressionKind::MethodCall { base, method_name, args } => {
+                // the method could read any field, so this has to be all set up first
+                if matches!(base.kind, ExpressionKind::ThisExpr) {
+                    self.check_fields_set(base.span, "calls a method on `this`");
+                }
+                let class = self.base_class(base, env, &format!("call method `{}`", method_name));
+                let method = class.as_ref().and_then(|c| {
+                    self.find_method(&c.name, method_name).map(|m| substitute_signature(m, &c.type_args))
+                });
+
+                let (class, method) = match (&class, &method) {
+                    (Some(class), Some(method)) => (class, method),
+                    (Some(class), None) => {
+                        self.error(ErrorCode::NoSuchMethod,
+                            format!("{} `{}` has no method `{}`", class.kind, class.name, method_name), expr.span);
+                        self.note(format!("`{}` declared here", class.name), class.span);
+                        // still check the arguments so their errors show up too
+                        for arg in args { self.eval_type(arg, env); }
+                        return None;
+                    }
+                    _ => {
+                        for arg in args { self.eval_type(arg, env); }
+                        return None;
+                    }
+                };
+
+                let callee = format!("`{}.{}`", class.name, method_name);
+                self.check_args(args, method, &callee, expr.span, env);
+
+                Some(method.return_type.clone())
+            }
+
+            // a variable holding a function value wins over a top level function with the same name
+            ExpressionKind::FunctionCall { function_name, args } if matches!(env.get(function_name), Some(Type::Function(..))) => {
+                let callee = Expression::new(ExpressionKind::Variable(function_name.clone()), expr.span);
+                let Some(Type::Function(params, ret)) = self.eval_type(&callee, env) else { unreachable!() };
+                if args.len() != params.len() {
+                    self.error(ErrorCode::WrongArgCount,
+                        format!("`{}` takes {} argument(s) but {} were given", function_name, params.len(), args.len()),
+                        expr.span);
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    if let Some(actual) = self.eval_type(arg, env)
+                        && let Some(param) = params.get(i)
+                        && !self.is_subtype(&actual, param) {
+                        self.mismatch(ErrorCode::ArgTypeMismatch,
+                            format!("argument {} of `{}` has the wrong type", i + 1, function_name),
+                            arg.span, param, &actual);
                     }
-                    Type::Int => panic!("Cannot call method on int"),
                 }
+                Some(*ret)
             }
 
-            Expression::FieldWrite { base, field_name, value } => {
+            ExpressionKind::FunctionCall { function_name, args } => {
+                let Some(function) = self.functions.get(function_name).cloned() else {
+                    self.error(ErrorCode::UnknownFunction, format!("unknown function `{}`", function_name), expr.span);
+                    for arg in args { self.eval_type(arg, env); }
+                    return None;
+                };
+                self.check_args(args, &function, &format!("`{}`", function_name), expr.span, env);
+                Some(function.return_type)
+            }
+
+            ExpressionKind::MethodRef { base, method_name } => {
+                if matches!(base.kind, ExpressionKind::ThisExpr) {
+                    self.check_fields_set(base.span, "takes a method of `this`");
+                }
+                let class = self.base_class(base, env, &format!("reference method `{}`", method_name))?;
+                let Some(method) = self.find_method(&class.name, method_name) else {
+                    self.error(ErrorCode::NoSuchMethod,
+                        format!("{} `{}` has no method `{}`", class.kind, class.name, method_name), expr.span);
+                    self.note(format!("`{}` declared here", class.name), class.span);
+                    return None;
+                };
+                Some(function_type(&substitute_signature(method, &class.type_args)))
+            }
+
+            ExpressionKind::Lambda(lambda) => Some(self.check_lambda(lambda, expr.span, env)),
+
+            ExpressionKind::FieldWrite { base, field_name, value } => {
                 // this is the same as field read but also check value type
-                let base_type = self.eval_type(base, env);
-                match &base_type {
-                    Type::ClassType(class_name) => {
-                        let class = self.classes.get(class_name).unwrap();
-                        let field_type = class.fields.iter()
-                            .find(|(n, _)| n == field_name)
-                            .map(|(_, t)| t)
-                            .unwrap_or_else(|| panic!("No field exists: {}", field_name));
-                        let val_type = self.eval_type(value, env);
-                        if val_type != *field_type {
-                            panic!("Field write type mismatch");
+                let class = self.base_class(base, env, &format!("write field `{}`", field_name));
+                let val_type = self.eval_type(value, env);
+                let class = class?;
+
+                let field = self.find_field(&class.name, field_name).map(|mut field| {
+                    field.typ = field.typ.substitute(&class.type_args);
+                    field
+                });
+                match (field, val_type) {
+                    (None, _) => {
+                        self.error(ErrorCode::NoSuchField,
+                            format!("{} `{}` has no field `{}`", class.kind, class.name, field_name), expr.span);
+                        self.note(format!("`{}` declared here", class.name), class.span);
+                        None
+                    }
+                    (Some(field), Some(val_type)) if !self.is_subtype(&val_type, &field.typ) => {
+                        self.mismatch(ErrorCode::FieldTypeMismatch,
+                            format!("cannot store into field `{}.{}`", class.name, field_name),
+                            value.span, &field.typ, &val_type);
+                        self.note(format!("field `{}` declared here", field_name), field.span);
+                        None
+                    }
+                    (_, val_type) => {
+                        if matches!(base.kind, ExpressionKind::ThisExpr) {
+                            self.non_null.insert(this_field(field_name));
                         }
                         val_type
                     }
-                    Type::Int => panic!("Cannot write field of int"),
                 }
             }
         }
     }
 
-    fn check_statement(&self, statement: &Statement, env: &HashMap<String, Type>, return_type: &Type) {
-        match statement {
+    /*
+    the body only sees its own arguments and the variables it captures, as they are right now.
+    it runs whenever the lambda gets called, so it starts outside of any loop, and in a constructor
+    the object has to be all set up before a lambda can take this along
+    */
+    fn check_lambda(&mut self, lambda: &Lambda, span: Span, env: &HashMap<String, Type>) -> Type {
+        let mut inner = HashMap::new();
+        let mut non_null = HashSet::new();
+        for capture in captures(lambda) {
+            let Some(typ) = env.get(&capture.name) else { continue };
+            if capture.name == "this" {
+                self.check_fields_set(capture.span, "captures `this` in a lambda");
+            }
+            if let Some(assigned) = capture.assigned {
+                self.error(ErrorCode::CapturedAssignment,
+                    format!("cannot assign to `{}` inside a lambda, the lambda only has a copy of it", capture.name),
+                    assigned);
+            }
+            inner.insert(capture.name.clone(), typ.clone());
+            if self.non_null.contains(&capture.name) {
+                non_null.insert(capture.name);
+            }
+        }
+        self.validate_type(&lambda.return_type, span);
+        for arg in &lambda.args {
+            self.validate_type(&arg.typ, arg.span);
+            inner.insert(arg.name.clone(), arg.typ.clone());
+            non_null.insert(arg.name.clone());
+        }
+
+        let outer_non_null = std::mem::replace(&mut self.non_null, non_null);
+        let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let outer_fields = std::mem::take(&mut self.constructor_fields);
+        self.check_block(&lambda.body, &inner, &lambda.return_type);
+        self.non_null = outer_non_null;
+        self.loop_depth = outer_loop_depth;
+        self.constructor_fields = outer_fields;
+
+        let args = lambda.args.iter().map(|arg| arg.typ.clone()).collect();
+        Type::Function(args, Box::new(lambda.return_type.clone()))
+    }
+
+    // the class or interface of the object a field/method is accessed on
+    // errors if base isnt an object, action says what we were trying to do with it
+    fn base_class(&mut self, base: &Expression, env: &HashMap<String, Type>, action: &str) -> Option<ObjectType> {
+        // a constructor can get at the fields of this before they are all set, it just cant pass this around
+        let typ = match (&base.kind, env.get("this")) {
+            (ExpressionKind::ThisExpr, Some(this)) => this.clone(),
+            _ => self.eval_type(base, env)?,
+        };
+        match typ {
+            Type::Nullable(inner) => {
+                self.mismatch(ErrorCode::NullableDereference,
+                    format!("cannot {} on a value that might be null", action),
+                    base.span, &inner, &Type::Nullable(inner.clone()));
+                None
+            }
+            // a T inside class Stack<T> could be anything, so it has no members to use
+            Type::ClassType(name) if self.type_params.contains(&name) => {
+                self.error(ErrorCode::NotAnObject,
+                    format!("cannot {} on a value of type parameter {}", action, name), base.span);
+                None
+            }
+            Type::ClassType(name) if self.enums.contains_key(&name) => {
+                self.error(ErrorCode::NotAnObject,
+                    format!("cannot {} on enum `{}`, use `match` to get at its fields", action, name), base.span);
+                None
+            }
+            Type::ClassType(name) => {
+                if let Some(class) = self.classes.get(&name) {
+                    Some(ObjectType { kind: "class", name, span: class.span, type_args: HashMap::new() })
+                } else {
+                    let interface = self.interfaces.get(&name)?;
+                    Some(ObjectType { kind: "interface", name, span: interface.span, type_args: HashMap::new() })
+                }
+            }
+            Type::Generic(name, args) => {
+                let type_args = self.type_arg_map(&name, &args);
+                let span = self.classes.get(&name)?.span;
+                Some(ObjectType { kind: "class", name, span, type_args })
+            }
+            other => {
+                self.error(ErrorCode::NotAnObject, format!("cannot {} on a value of type {}", action, other), base.span);
+                None
+            }
+        }
+    }
+
+    // the element type of the array being indexed, what says what we were doing with it
+    // a str reads like an int[] of character codes, but only when allow_str since strings are immutable
+    fn array_element(&mut self, array: &Expression, env: &HashMap<String, Type>, what: &str, allow_str: bool) -> Option<Type> {
+        match self.eval_type(array, env)? {
+            Type::Array(element) => Some(*element),
+            Type::Str if allow_str => Some(Type::Int),
+            Type::Nullable(inner) if matches!(*inner, Type::Array(_)) || (allow_str && *inner == Type::Str) => {
+                self.mismatch(ErrorCode::NullableDereference,
+                    format!("cannot {} a value that might be null", what),
+                    array.span, &inner, &Type::Nullable(inner.clone()));
+                None
+            }
+            other => {
+                self.error(ErrorCode::NotAnArray, format!("cannot {} a value of type {}", what, other), array.span);
+                None
+            }
+        }
+    }
+
+    // arguments to a method or constructor, callee is how to name it in messages, ex: `A.m`
+    fn check_args(&mut self, args: &[Expression], method: &MethodSignature, callee: &str,
+                  span: Span, env: &HashMap<String, Type>) {
+        if args.len() != method.args.len() {
+            self.error(ErrorCode::WrongArgCount,
+                format!("{} takes {} argument(s) but {} were given", callee, method.args.len(), args.len()),
+                span);
+            self.note(format!("`{}` declared here", method.name), method.span);
+        }
+        for (arg_expr, arg) in args.iter().zip(method.args.iter()) {
+            if let Some(actual) = self.eval_type(arg_expr, env)
+                && !self.is_subtype(&actual, &arg.typ) {
+                self.mismatch(ErrorCode::ArgTypeMismatch,
+                    format!("argument `{}` of {} has the wrong type", arg.name, callee),
+                    arg_expr.span, &arg.typ, &actual);
+                self.note(format!("argument `{}` declared here", arg.name), arg.span);
+            }
+        }
+        for arg_expr in args.iter().skip(method.args.len()) {
+            self.eval_type(arg_expr, env);
+        }
+    }
+
+    fn check_index(&mut self, index: &Expression, env: &HashMap<String, Type>, what: &str) {
+        if let Some(typ) = self.eval_type(index, env)
+            && typ != Type::Int {
+            self.mismatch(ErrorCode::NonIntIndex, format!("array {} must be an int", what),
+                index.span, &Type::Int, &typ);
+        }
+    }
+
+    // operands of and/or/! have to be bools, everything else that takes operands wants ints
+    fn expect_operand(&mut self, operand: &Expression, typ: &Option<Type>, expected: &Type, message: String) {
+        if let Some(typ) = typ
+            && typ != expected {
+            let code = if *expected == Type::Bool { ErrorCode::NonBoolOperand } else { ErrorCode::NonIntOperand };
+            self.mismatch(code, message, operand.span, expected, typ);
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Expression, env: &HashMap<String, Type>, construct: &str) {
+        if let Some(typ) = self.eval_type(condition, env)
+            && typ != Type::Bool {
+            self.mismatch(ErrorCode::NonBoolCondition,
+                format!("`{}` condition must be a bool", construct),
+                condition.span, &Type::Bool, &typ);
+        }
+    }
+
+    // statements in order, warning once about anything after a return, break, or continue
+    // every block is its own scope, declarations in it go away at the end and can shadow outer names
+    fn check_block(&mut self, statements: &[Statement], env: &HashMap<String, Type>, return_type: &Type) {
+        let mut scope = env.clone();
+        let outer_non_null = self.non_null.clone();
+        // names declared in this block so far, and where
+        let mut declared: HashMap<String, Span> = HashMap::new();
+        let mut exit: Option<Span> = None;
+        let mut warned = false;
+        for statement in statements {
+            if let Some(exit) = exit
+                && !warned {
+                warned = true;
+                self.warnings.push(TypeError {
+                    code: ErrorCode::UnreachableCode,
+                    message: "unreachable statement".to_string(),
+                    span: statement.span,
+                    expected: None,
+                    found: None,
+                    notes: vec![("any code following this statement is unreachable".to_string(), exit)],
+                });
+            }
+            // still check the dead code, it can have errors of its own
+            match &statement.kind {
+                StatementKind::Declaration { name, typ, value } => {
+                    if let Some(first) = declared.insert(name.clone(), statement.span) {
+                        self.error(ErrorCode::VariableRedeclared,
+                            format!("`{}` is already declared in this block", name), statement.span);
+                        self.note(format!("`{}` first declared here", name), first);
+                    }
+                    match self.check_declaration(name, typ, value, statement.span, &scope) {
+                        Some(typ) => scope.insert(name.clone(), typ),
+                        // couldnt work out a type, at least dont let uses fall through to an outer x
+                        None => scope.remove(name),
+                    };
+                }
+                _ => self.check_statement(statement, &scope, return_type),
+            }
+            if exit.is_none() && always_exits(statement) {
+                exit = Some(statement.span);
+            }
+        }
+
+        // whatever was learned about a shadowed name goes back to being about the outer one
+        for name in declared.keys() {
+            if outer_non_null.contains(name) {
+                self.non_null.insert(name.clone());
+            } else {
+                self.non_null.remove(name);
+            }
+        }
+    }
+
+    // the type a declared variable ends up with, the written one or else the initializer's
+    // the initializer is checked before the name exists, so var x = x + 1 reads an outer x
+    fn check_declaration(&mut self, name: &str, typ: &Option<Type>, value: &Expression, span: Span,
+                         env: &HashMap<String, Type>) -> Option<Type> {
+        let value_type = self.eval_type(value, env);
+        self.bind(name, value_type.as_ref());
+        let Some(typ) = typ else { return value_type };
+
+        self.validate_type(typ, span);
+        if let Some(value_type) = value_type
+            && !self.is_subtype(&value_type, typ) {
+            self.mismatch(ErrorCode::AssignmentMismatch, format!("cannot initialize `{}`", name),
+                value.span, typ, &value_type);
+        }
+        Some(typ.clone())
+    }
+
+    // a branch of an if with what its condition proved, returns what is known at its end
+    // or None when it never gets to the end
+    fn check_branch(&mut self, body: &[Statement], proved: HashSet<String>, env: &HashMap<String, Type>,
+                    return_type: &Type) -> Option<HashSet<String>> {
+        self.non_null.extend(proved);
+        self.check_block(body, env, return_type);
+        if body.iter().any(always_exits) { None } else { Some(self.non_null.clone()) }
+    }
+
+    // the second time around a loop, a nullable variable the body assigns could be null again
+    fn forget_loop_assignments(&mut self, body: &[Statement], env: &HashMap<String, Type>) {
+        let mut assigned = HashSet::new();
+        assigned_variables(body, &mut assigned);
+        for name in assigned {
+            if let Some(Type::Nullable(_)) = env.get(&name) {
+                self.non_null.remove(&name);
+            }
+        }
+    }
+
+    // only objects can be thrown, the catches tell them apart by their vtable
+    fn is_throwable(&self, typ: &Type) -> bool {
+        match typ {
+            Type::ClassType(name) => !self.type_params.contains(name)
+                && (self.classes.contains_key(name) || self.interfaces.contains_key(name)),
+            Type::Generic(..) => true,
+            _ => false,
+        }
+    }
+
+    fn check_throw(&mut self, value: &Expression, env: &HashMap<String, Type>) {
+        match self.eval_type(value, env) {
+            Some(Type::Nullable(inner)) if self.is_throwable(&inner) => {
+                self.mismatch(ErrorCode::NullableDereference, "cannot throw a value that might be null".to_string(),
+                    value.span, &inner, &Type::Nullable(inner.clone()));
+            }
+            Some(typ) if !self.is_throwable(&typ) => {
+                self.error(ErrorCode::NotThrowable,
+                    format!("cannot throw a value of type {}, only objects of a class", typ), value.span);
+            }
+            _ => {}
+        }
+    }
+
+    /*
+    the exception could come from anywhere in the try block, so a catch only knows what was known
+    before it, minus the nullable variables the block assigns. after the whole thing only what holds
+    at the end of the block and of every catch that falls through is known, like the branches of an if
+    */
+    fn check_try(&mut self, body: &[Statement], catches: &[Catch], env: &HashMap<String, Type>, return_type: &Type) {
+        let before = self.non_null.clone();
+        let mut after = self.check_branch(body, HashSet::new(), env, return_type);
+
+        let mut assigned = HashSet::new();
+        assigned_variables(body, &mut assigned);
+        let caught: HashSet<String> = before.iter()
+            .filter(|name| !(assigned.contains(*name) && matches!(env.get(*name), Some(Type::Nullable(_)))))
+            .cloned()
+            .collect();
+
+        for (i, catch) in catches.iter().enumerate() {
+            self.non_null = caught.clone();
+            let mut scope = env.clone();
+            if self.validate_type(&catch.typ, catch.span) {
+                if self.is_throwable(&catch.typ) {
+                    scope.insert(catch.name.clone(), catch.typ.clone());
+                    self.non_null.insert(catch.name.clone());
+                } else {
+                    self.error(ErrorCode::NotThrowable,
+                        format!("cannot catch {}, only objects of a class are thrown", catch.typ), catch.span);
+                }
+            }
+
+            // a catch for a subclass after one for its superclass never gets anything
+            if let Some(earlier) = catches[..i].iter().find(|earlier| self.is_subtype(&catch.typ, &earlier.typ)) {
+                self.warnings.push(TypeError {
+                    code: ErrorCode::UnreachableCode,
+                    message: "unreachable catch".to_string(),
+                    span: catch.span,
+                    expected: None,
+                    found: None,
+                    notes: vec![(format!("{} is already caught here", catch.typ), earlier.span)],
+                });
+            }
+
+            let end = self.check_branch(&catch.body, HashSet::new(), &scope, return_type).map(|mut end| {
+                if before.contains(&catch.name) {
+                    end.insert(catch.name.clone());
+                } else {
+                    end.remove(&catch.name);
+                }
+                end
+            });
+            after = join(after, end);
+        }
+
+        self.non_null = after.unwrap_or(before);
+    }
+
+    // the enum a match is on, None (already reported) for anything else
+    fn matched_enum(&mut self, value: &Expression, env: &HashMap<String, Type>) -> Option<Enum> {
+        match self.eval_type(value, env)? {
+            Type::ClassType(name) if self.enums.contains_key(&name) => Some(self.enums[&name].clone()),
+            Type::Nullable(inner) if matches!(inner.as_ref(), Type::ClassType(name) if self.enums.contains_key(name)) => {
+                self.mismatch(ErrorCode::NullableDereference, "cannot match on a value that might be null".to_string(),
+                    value.span, &inner, &Type::Nullable(inner.clone()));
+                None
+            }
+            other => {
+                self.error(ErrorCode::NotAnEnum, format!("cannot match on a value of type {}, only on an enum", other),
+                    value.span);
+                None
+            }
+        }
+    }
+
+    /*
+    every variant needs an arm, or there has to be a _ arm to take the ones left over.
+    the fields an arm binds are in scope for just that arm, and like the branches of an if
+    only what holds at the end of every arm that falls through is known after the match
+    */
+    fn check_match(&mut self, value: &Expression, arms: &[MatchArm], span: Span,
+                   env: &HashMap<String, Type>, return_type: &Type) {
+        let declared = self.matched_enum(value, env);
+        let before = self.non_null.clone();
+        let mut after: Option<HashSet<String>> = None;
+        // variant name -> the arm that handles it
+        let mut covered: HashMap<String, Span> = HashMap::new();
+        let mut wildcard: Option<Span> = None;
+
+        for arm in arms {
+            let mut scope = env.clone();
+            self.non_null = before.clone();
+
+            let earlier = match &arm.variant {
+                Some(name) => wildcard.or(covered.get(name).copied()),
+                None => wildcard.or_else(|| {
+                    let all = declared.as_ref()?.variants.iter().all(|v| covered.contains_key(&v.name));
+                    if all { arms.first().map(|first| first.span) } else { None }
+                }),
+            };
+            if let Some(earlier) = earlier {
+                self.warnings.push(TypeError {
+                    code: ErrorCode::UnreachableCode,
+                    message: "unreachable match arm".to_string(),
+                    span: arm.span,
+                    expected: None,
+                    found: None,
+                    notes: vec![("every value it could match is already handled from here".to_string(), earlier)],
+                });
+            }
+
+            match (&arm.variant, &declared) {
+                (None, _) => {
+                    wildcard.get_or_insert(arm.span);
+                }
+                (Some(name), Some(declared)) => match declared.variant(name) {
+                    None => {
+                        self.error(ErrorCode::UnknownVariant,
+                            format!("enum `{}` has no variant `{}`", declared.name, name), arm.span);
+                        self.note(format!("`{}` declared here", declared.name), declared.span);
+                    }
+                    Some((_, variant)) => {
+                        covered.entry(name.clone()).or_insert(arm.span);
+                        if arm.bindings.len() != variant.fields.len() {
+                            self.error(ErrorCode::WrongArgCount,
+                                format!("variant `{}.{}` has {} field(s) but {} name(s) were given",
+                                    declared.name, name, variant.fields.len(), arm.bindings.len()),
+                                arm.span);
+                            self.note(format!("`{}` declared here", name), variant.span);
+                        }
+                        let mut bound = HashSet::new();
+                        for (binding, field) in arm.bindings.iter().zip(&variant.fields) {
+                            if binding == "_" {
+                                continue;
+                            }
+                            if !bound.insert(binding) {
+                                self.error(ErrorCode::VariableRedeclared,
+                                    format!("`{}` is bound twice in this arm", binding), arm.span);
+                            }
+                            scope.insert(binding.clone(), field.typ.clone());
+                            self.non_null.insert(binding.clone());
+                        }
+                    }
+                },
+                // the value itself was already an error
+                (Some(_), None) => {}
+            }
+
+            let end = self.check_branch(&arm.body, HashSet::new(), &scope, return_type).map(|mut end| {
+                // the bindings go away with the arm, an outer variable with the same name is back
+                for binding in &arm.bindings {
+                    if before.contains(binding) {
+                        end.insert(binding.clone());
+                    } else {
+                        end.remove(binding);
+                    }
+                }
+                end
+            });
+            after = join(after, end);
+        }
+
+        if let Some(declared) = &declared
+            && wildcard.is_none() {
+            let missing: Vec<String> = declared.variants.iter()
+                .filter(|variant| !covered.contains_key(&variant.name))
+                .map(|variant| format!("`{}`", variant.name))
+                .collect();
+            if !missing.is_empty() {
+                self.error(ErrorCode::NonExhaustiveMatch,
+                    format!("match on `{}` does not handle {}, add arms for them or a `_` arm",
+                        declared.name, missing.join(", ")),
+                    span);
+                self.note(format!("`{}` declared here", declared.name), declared.span);
+            }
+        }
+
+        self.non_null = after.unwrap_or(before);
+    }
+
+    fn check_statement(&mut self, statement: &Statement, env: &HashMap<String, Type>, return_type: &Type) {
+        match &statement.kind {
+            StatementKind::Declaration { .. } => unreachable!("declarations are checked by check_block"),
+
             /*
-            print is well-typed if its argument is an int
-            if, ifonly, and while are well-typed if their bodies/branches are well-typed, and the condition has type int.
+            print is well-typed if its argument is an int, a bool, or a str
+            if, ifonly, and while are well-typed if their bodies/branches are well-typed, and the condition has type bool.
             A variable assignment is well-typed if the type of the expression matches the type of the variable.
-            Field updates are similar: find the type of the field being updated, and check that the expression being stored there has the same type 
+            Field updates are similar: find the type of the field being updated, and check that the expression being stored there has the same type
             */
-            
-            Statement::Print(expr) => {
-                if self.eval_type(expr, env) != Type::Int {
-                    panic!("print requires int");
+
+            StatementKind::Print(expr) => {
+                if let Some(typ) = self.eval_type(expr, env)
+                    && typ != Type::Int && typ != Type::Bool && typ != Type::Str {
+                    self.mismatch(ErrorCode::PrintNonInt, "print requires an int, a bool, or a str".to_string(),
+                        expr.span, &Type::Int, &typ);
                 }
             }
-            
-            
-            Statement::Return(expr) => {
-                let t = self.eval_type(expr, env);
-                if t != *return_type {
-                    panic!("Return type mismatch");
+
+
+            StatementKind::Return(expr) => {
+                if let Some(t) = self.eval_type(expr, env)
+                    && !self.is_subtype(&t, return_type) {
+                    self.mismatch(ErrorCode::ReturnTypeMismatch, "returned value has the wrong type".to_string(),
+                        expr.span, return_type, &t);
                 }
+                self.check_fields_set(statement.span, "returns");
             }
 
             // just match the expression and var type
-            Statement::Assignment { variable, expression } => {
-                let var_type = env.get(variable).unwrap_or_else(|| panic!("Undefined: {}", variable));
+            StatementKind::Assignment { variable, expression } => {
                 let expr_type = self.eval_type(expression, env);
-                if expr_type != *var_type {
-                    panic!("Assignment type mismatch for {}", variable);
+                match (env.get(variable), expr_type) {
+                    (None, _) => {
+                        self.error(ErrorCode::UndefinedVariable,
+                            format!("assignment to undefined variable `{}`", variable), statement.span);
+                    }
+                    (Some(var_type), Some(expr_type)) if !self.is_subtype(&expr_type, var_type) => {
+                        self.mismatch(ErrorCode::AssignmentMismatch,
+                            format!("cannot assign to `{}`", variable),
+                            expression.span, var_type, &expr_type);
+                    }
+                    (Some(_), expr_type) => self.bind(variable, expr_type.as_ref()),
                 }
             }
 
             // straight forward
-            Statement::FieldWrite { base, field, value } => {
-                let expr = Expression::FieldWrite {
+            StatementKind::FieldWrite { base, field, value } => {
+                let expr = Expression::new(ExpressionKind::FieldWrite {
                     base: Box::new(base.clone()),
                     field_name: field.clone(),
                     value: Box::new(value.clone()),
-                };
+                }, statement.span);
                 self.eval_type(&expr, env);
             }
 
-            Statement::Discard(expr) => {
+            StatementKind::ArrayWrite { array, index, value } => {
+                let element = self.array_element(array, env, "store into", false);
+                self.check_index(index, env, "index");
+                if let (Some(element), Some(val_type)) = (element, self.eval_type(value, env))
+                    && !self.is_subtype(&val_type, &element) {
+                    self.mismatch(ErrorCode::ArrayElementMismatch,
+                        format!("cannot store into an array of {}", element),
+                        value.span, &element, &val_type);
+                }
+            }
+
+            StatementKind::Discard(expr) => {
                 self.eval_type(expr, env);
             }
 
-            Statement::If { condition, then_body, else_body } => {
-                if self.eval_type(condition, env) != Type::Int {
-                    panic!("'If' condition must be int");
-                }
-                for s in then_body { self.check_statement(s, env, return_type); }
-                for s in else_body { self.check_statement(s, env, return_type); }
+            /*
+            a null test narrows the variable for the branch where it passed:
+                if p == null:A?: { <p is an A?> } else { <p is an A> }
+            afterwards only what holds at the end of every branch that can fall through is kept,
+            so after ifonly p == null:A?: { return 0 } p stays an A
+            */
+            StatementKind::If { condition, then_body, else_body } => {
+                self.check_condition(condition, env, "if");
+                let (when_true, when_false) = null_tests(condition);
+                let before = self.non_null.clone();
+                let then_end = self.check_branch(then_body, when_true, env, return_type);
+                self.non_null = before.clone();
+                let else_end = self.check_branch(else_body, when_false, env, return_type);
+                self.non_null = join(then_end, else_end).unwrap_or(before);
             }
 
-            Statement::IfOnly { condition, body } => {
-                if self.eval_type(condition, env) != Type::Int {
-                    panic!("'IfOnly' condition must be int");
+            StatementKind::IfOnly { condition, body } => {
+                self.check_condition(condition, env, "ifonly");
+                let (when_true, when_false) = null_tests(condition);
+                let before = self.non_null.clone();
+                let body_end = self.check_branch(body, when_true, env, return_type);
+                let skipped = before.union(&when_false).cloned().collect();
+                self.non_null = join(body_end, Some(skipped)).unwrap_or(before);
+            }
+
+            StatementKind::While { condition, body } => {
+                self.forget_loop_assignments(body, env);
+                self.check_condition(condition, env, "while");
+                let (when_true, _) = null_tests(condition);
+                let before = self.non_null.clone();
+                self.loop_depth += 1;
+                self.check_branch(body, when_true, env, return_type);
+                self.loop_depth -= 1;
+                // the body might not run at all, or leave through a break
+                self.non_null = before;
+            }
+
+            StatementKind::For { variable, from, to, body } => {
+                match env.get(variable) {
+                    None => {
+                        self.error(ErrorCode::UndefinedVariable,
+                            format!("undefined loop variable `{}`", variable), statement.span);
+                    }
+                    Some(var_type) if *var_type != Type::Int => {
+                        self.mismatch(ErrorCode::AssignmentMismatch,
+                            format!("loop variable `{}` must be an int", variable),
+                            statement.span, &Type::Int, var_type);
+                    }
+                    _ => {}
                 }
-                for s in body { self.check_statement(s, env, return_type); }
+                for bound in [from, to] {
+                    let typ = self.eval_type(bound, env);
+                    self.expect_operand(bound, &typ, &Type::Int, "`for` bounds must be ints".to_string());
+                }
+                self.forget_loop_assignments(body, env);
+                let before = self.non_null.clone();
+                self.loop_depth += 1;
+                self.check_block(body, env, return_type);
+                self.loop_depth -= 1;
+                self.non_null = before;
             }
 
-            Statement::While { condition, body } => {
-                if self.eval_type(condition, env) != Type::Int {
-                    panic!("'While' condition must be a int");
+            StatementKind::Match { value, arms } => self.check_match(value, arms, statement.span, env, return_type),
+
+            StatementKind::Throw(value) => self.check_throw(value, env),
+
+            StatementKind::Try { body, catches } => self.check_try(body, catches, env, return_type),
+
+            StatementKind::Break | StatementKind::Continue => {
+                if self.loop_depth == 0 {
+                    let keyword = if matches!(statement.kind, StatementKind::Break) { "break" } else { "continue" };
+                    self.error(ErrorCode::OutsideLoop, format!("`{}` outside of a loop", keyword), statement.span);
                 }
-                for s in body { self.check_statement(s, env, return_type); }
             }
         }
     }
 }
 
+// control never gets past this statement: a return, break, continue, or throw,
+// or an if, match, or try where every branch does
+pub fn always_exits(statement: &Statement) -> bool {
+    match &statement.kind {
+        StatementKind::Return(_) | StatementKind::Break | StatementKind::Continue | StatementKind::Throw(_) => true,
+        StatementKind::If { then_body, else_body, .. } =>
+            then_body.iter().any(always_exits) && else_body.iter().any(always_exits),
+        // a match without every variant covered is an error anyway
+        StatementKind::Match { arms, .. } =>
+            !arms.is_empty() && arms.iter().all(|arm| arm.body.iter().any(always_exits)),
+        StatementKind::Try { body, catches } =>
+            body.iter().any(always_exits) && catches.iter().all(|catch| catch.body.iter().any(always_exits)),
+        _ => false,
+    }
+}
+
+// a method seen through a Stack<int>, with every T in its arguments and return type made an int
+fn substitute_signature(mut signature: MethodSignature, type_args: &HashMap<String, Type>) -> MethodSignature {
+    for arg in &mut signature.args {
+        arg.typ = arg.typ.substitute(type_args);
+    }
+    signature.return_type = signature.return_type.substitute(type_args);
+    signature
+}
+
+// the values that have to be set before they are used, since zeroed memory would mean null
+fn holds_object(typ: &Type) -> bool {
+    matches!(typ, Type::ClassType(_) | Type::Generic(..) | Type::Function(..) | Type::Str | Type::Array(_))
+}
+
+// the type of a method or function used as a value, (int, A) -> bool
+fn function_type(signature: &MethodSignature) -> Type {
+    let args = signature.args.iter().map(|arg| arg.typ.clone()).collect();
+    Type::Function(args, Box::new(signature.return_type.clone()))
+}
+
+// how a field of this is tracked in non_null while a constructor runs, cant clash with a variable name
+fn this_field(field: &str) -> String {
+    format!("this.{}", field)
+}
+
+// variables a condition proves hold an object when it is true, and when it is false.
+// a comparison of a variable against null counts, and so do and, or, and ! made out of those
+fn null_tests(condition: &Expression) -> (HashSet<String>, HashSet<String>) {
+    match &condition.kind {
+        ExpressionKind::Binop { op: op @ (Operator::Equals | Operator::NotEquals), lhs, rhs } => {
+            let tested: HashSet<String> = match (&lhs.kind, &rhs.kind) {
+                (ExpressionKind::Variable(name), ExpressionKind::Null(_))
+                | (ExpressionKind::Null(_), ExpressionKind::Variable(name)) => HashSet::from([name.clone()]),
+                _ => HashSet::new(),
+            };
+            if *op == Operator::NotEquals { (tested, HashSet::new()) } else { (HashSet::new(), tested) }
+        }
+        ExpressionKind::Binop { op: Operator::And, lhs, rhs } => {
+            let ((lhs_true, _), (rhs_true, _)) = (null_tests(lhs), null_tests(rhs));
+            (lhs_true.union(&rhs_true).cloned().collect(), HashSet::new())
+        }
+        ExpressionKind::Binop { op: Operator::Or, lhs, rhs } => {
+            let ((_, lhs_false), (_, rhs_false)) = (null_tests(lhs), null_tests(rhs));
+            (HashSet::new(), lhs_false.union(&rhs_false).cloned().collect())
+        }
+        ExpressionKind::Unary { op: UnaryOperator::Not, operand } => {
+            let (when_true, when_false) = null_tests(operand);
+            (when_false, when_true)
+        }
+        _ => (HashSet::new(), HashSet::new()),
+    }
+}
+
+// what is known where two paths meet, None for a path that never gets there
+fn join(a: Option<HashSet<String>>, b: Option<HashSet<String>>) -> Option<HashSet<String>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(&b).cloned().collect()),
+        (a, b) => a.or(b),
+    }
+}
+
+// every variable assigned anywhere in these statements, nested blocks included
+fn assigned_variables(statements: &[Statement], assigned: &mut HashSet<String>) {
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Assignment { variable, .. } => {
+                assigned.insert(variable.clone());
+            }
+            StatementKind::If { then_body, else_body, .. } => {
+                assigned_variables(then_body, assigned);
+                assigned_variables(else_body, assigned);
+            }
+            StatementKind::IfOnly { body, .. }
+            | StatementKind::While { body, .. }
+            | StatementKind::For { body, .. } => assigned_variables(body, assigned),
+            StatementKind::Match { arms, .. } => {
+                for arm in arms {
+                    assigned_variables(&arm.body, assigned);
+                }
+            }
+            StatementKind::Try { body, catches } => {
+                assigned_variables(body, assigned);
+                for catch in catches {
+                    assigned_variables(&catch.body, assigned);
+                }
+            }
+            _ => {}
+        }
+    }
+}